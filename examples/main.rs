@@ -1,16 +1,17 @@
-use clihistory::{CliHistory, CliHistorySettings};
+use clihistory::{CliHistory, CliHistorySettings, HistoryEntry};
 
 fn main() {
     // Setup
-    let mut settings = CliHistorySettings::new();
-    settings.set_label("Enter some text:");
-    settings.set_max_size(100);
-    settings.set_die_on_exit();
-    settings.set_log_to_file("history.txt");
-    settings.set_max_size_log_file(100);
+    let settings = CliHistorySettings::builder()
+        .label("Enter some text:")
+        .max_size(100)
+        .die_on_exit(true)
+        .log_to_file("history.txt")
+        .max_size_log_file(100)
+        .build();
 
     // Initialize
-    let mut cli_history = CliHistory::new(&settings);
+    let mut cli_history = CliHistory::new(settings);
 
     // Start input prompt handler
     let input: String = cli_history.launch_navigator(|command: &str| {
@@ -18,7 +19,7 @@ fn main() {
     });
 
     // Get the collected data
-    let history: &mut Vec<String> = cli_history.get_history();
+    let history: &mut Vec<HistoryEntry> = cli_history.get_history();
 
     println!();
 
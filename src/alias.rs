@@ -0,0 +1,156 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::collections::{BTreeMap, HashSet};
+
+// Replace an alias in the first word of `line`, aliases may refer to other aliases
+// but never to themselves. Returns None if the first word is no alias.
+pub(crate) fn expand_alias(line: &str, aliases: &BTreeMap<String, String>) -> Option<String> {
+    let mut line = line.to_string();
+    let mut seen = HashSet::new();
+
+    loop {
+        let trimmed = line.trim_start();
+        let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+        let name = &trimmed[..end];
+
+        let value = match aliases.get(name) {
+            Some(value) if seen.insert(name.to_string()) => value,
+            _ => break
+        };
+
+        line = format!("{}{}", value, &trimmed[end..]);
+    }
+
+    (!seen.is_empty()).then_some(line)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Replace $NAME and ${NAME} with the value `lookup` finds, unknown variables
+// become empty like in a shell. Nothing is replaced within single quotes, \$ is a literal $.
+pub(crate) fn expand_variables<F>(line: &str, lookup: F) -> String
+where F: Fn(&str) -> Option<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut in_quotes = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\\' && chars.get(i + 1) == Some(&'$') && !in_quotes {
+            out.push('$');
+            i += 2;
+            continue;
+        }
+        if c == '\'' {
+            in_quotes = !in_quotes;
+        }
+        if c != '$' || in_quotes {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+
+        // ${NAME}
+        if chars.get(i + 1) == Some(&'{')
+            && let Some(len) = chars[i + 2..].iter().position(|&c| c == '}') {
+            let name: String = chars[i + 2..i + 2 + len].iter().collect();
+            if !name.is_empty() && name.chars().all(is_name_char) {
+                out.push_str(&lookup(&name).unwrap_or_default());
+                i += len + 3;
+                continue;
+            }
+        }
+
+        // $NAME
+        let len = chars[i + 1..].iter().take_while(|&&c| is_name_char(c)).count();
+        if len > 0 && !chars[i + 1].is_ascii_digit() {
+            let name: String = chars[i + 1..i + 1 + len].iter().collect();
+            out.push_str(&lookup(&name).unwrap_or_default());
+            i += len + 1;
+            continue;
+        }
+
+        out.push(c);
+        i += 1;
+    }
+
+    out
+}
+
+// Split `name=value` of the alias built-in, quotes around the whole value are removed
+pub(crate) fn parse_alias(definition: &str) -> Option<(String, String)> {
+    let (name, value) = definition.split_once('=')?;
+    let name = name.trim();
+
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let value = value.trim();
+    let unquoted = ['\'', '"'].iter()
+        .find_map(|&quote| value.strip_prefix(quote).and_then(|value| value.strip_suffix(quote)))
+        .unwrap_or(value);
+
+    Some((name.to_string(), unquoted.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_alias() {
+        let aliases: BTreeMap<String, String> = [("ll", "ls -l"), ("ls", "list --color"), ("loop", "loop x")]
+            .iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+
+        assert_eq!(expand_alias("ll src", &aliases), Some("list --color -l src".to_string()));
+        assert_eq!(expand_alias("loop", &aliases), Some("loop x".to_string()));
+        assert_eq!(expand_alias("echo ll", &aliases), None);
+    }
+
+    #[test]
+    fn test_expand_variables() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "x" => Some("1".to_string()),
+            _ => None
+        };
+
+        assert_eq!(expand_variables("cd $HOME/src ${x}2", lookup), "cd /home/me/src 12");
+        assert_eq!(expand_variables("echo '$HOME' \\$x $UNSET. $ $1", lookup), "echo '$HOME' $x . $ $1");
+    }
+
+    #[test]
+    fn test_parse_alias() {
+        assert_eq!(parse_alias("ll=list -l"), Some(("ll".to_string(), "list -l".to_string())));
+        assert_eq!(parse_alias("g='git status'"), Some(("g".to_string(), "git status".to_string())));
+        assert_eq!(parse_alias("bad name=x"), None);
+        assert_eq!(parse_alias("ll"), None);
+    }
+}
@@ -0,0 +1,362 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::io;
+use console::Term;
+
+use super::alias::{expand_alias, parse_alias};
+use super::history::{self, HistoryEntry};
+use super::tokenize::tokenize;
+use super::CliHistory;
+
+const HISTORY_USAGE: &str = "history: usage: history [n] | -c | -d <n> | -w | -r";
+const FC_USAGE: &str = "fc: usage: fc [n | -n | string]";
+const UNALIAS_USAGE: &str = "unalias: usage: unalias <name>...";
+
+// Names of the built-in history and alias commands, for completion
+pub(crate) const HISTORY_BUILTINS: [&str; 2] = ["history", "fc"];
+pub(crate) const ALIAS_BUILTINS: [&str; 2] = ["alias", "unalias"];
+
+// What the navigator has to do after a line was checked for built-in commands
+pub(crate) enum Builtin {
+    // The line is no built-in command
+    None,
+    // The built-in command ran
+    Done,
+    // Load this command into the editor (fc)
+    Edit(String),
+}
+
+// Errors of built-in commands are reported on stderr
+fn report(result: io::Result<Builtin>) -> Builtin {
+    match result {
+        Ok(builtin) => builtin,
+        Err(err) => {
+            let _ = Term::stderr().write_line(&err.to_string());
+            Builtin::Done
+        }
+    }
+}
+
+impl CliHistory {
+    // Run `line` if it is one of the enabled built-in commands. Aliases are replaced first
+    // like in bash, the history records the line as typed.
+    pub(crate) fn run_builtin(&mut self, term: &Term, line: &str) -> Builtin {
+        let expanded = expand_alias(line, self.settings.aliases());
+        let command = expanded.as_deref().unwrap_or(line);
+        let name = command.split_whitespace().next().unwrap_or_default();
+        let enabled = (self.settings.history_builtins && HISTORY_BUILTINS.contains(&name))
+            || (self.settings.alias_builtins && ALIAS_BUILTINS.contains(&name));
+
+        if !enabled {
+            return Builtin::None;
+        }
+
+        if name == "alias" {
+            // Definitions are taken as written: alias ll=list -l
+            let definition = command.trim_start()[name.len()..].trim();
+            let result = self.alias_builtin(term, line, definition);
+            return report(result.map(|_| Builtin::Done));
+        }
+
+        let args = match tokenize(command) {
+            Ok(args) => args,
+            Err(_) => return Builtin::None // Left to the callback to report
+        };
+
+        let result = match args.split_first() {
+            Some((name, args)) if name == "history" => {
+                // Recorded before it runs like in bash, `history` lists itself
+                self.record_builtin(term, line);
+                self.history_builtin(term, args).map(|_| Builtin::Done)
+            }
+            Some((name, args)) if name == "unalias" => {
+                self.record_builtin(term, line);
+                self.unalias_builtin(args).map(|_| Builtin::Done)
+            }
+            Some((name, args)) if name == "fc" => self.fc_builtin(args).map(Builtin::Edit),
+            _ => return Builtin::None
+        };
+
+        report(result)
+    }
+
+    fn record_builtin(&mut self, term: &Term, line: &str) {
+        if self.value_add_history(line) {
+            self.record_last_entry(term);
+        }
+    }
+
+    // alias | alias name | alias name=value
+    fn alias_builtin(&mut self, term: &Term, line: &str, definition: &str) -> io::Result<()> {
+        self.record_builtin(term, line);
+
+        if definition.is_empty() {
+            for (name, value) in self.settings.aliases() {
+                term.write_line(&format!("alias {}='{}'", name, value))?;
+            }
+            return Ok(());
+        }
+
+        if !definition.contains('=') {
+            let value = self.settings.aliases().get(definition)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("alias: {}: not found", definition)))?;
+            return term.write_line(&format!("alias {}='{}'", definition, value));
+        }
+
+        let (name, value) = parse_alias(definition)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("alias: `{}': invalid alias name", definition)))?;
+        self.settings.set_alias(&name, &value);
+        Ok(())
+    }
+
+    fn unalias_builtin(&mut self, names: &[String]) -> io::Result<()> {
+        if names.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, UNALIAS_USAGE));
+        }
+
+        for name in names {
+            self.settings.remove_alias(name)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("unalias: {}: not found", name)))?;
+        }
+
+        Ok(())
+    }
+
+    // history [n] | -c | -d <n> | -w | -r
+    fn history_builtin(&mut self, term: &Term, args: &[String]) -> io::Result<()> {
+        let usage = || io::Error::new(io::ErrorKind::InvalidInput, HISTORY_USAGE);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+        match args.as_slice() {
+            [] => self.list_history(term, self.history.len()),
+            ["-c"] => {
+                // Only the in-memory history, -w clears the history file as well
+                self.history.clear();
+                self.deleted.clear();
+                self.cleared = true;
+                Ok(())
+            }
+            ["-d", number] => {
+                let number: usize = number.parse().map_err(|_| usage())?;
+                if number == 0 || number > self.history.len() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("history: {}: position out of range", number)));
+                }
+
+                // Remembered so -w removes it from the history file as well
+                let entry = self.history.remove(number - 1);
+                self.deleted.push(entry);
+                Ok(())
+            }
+            ["-w"] => self.write_history(),
+            ["-r"] => self.load_history().map(|_| ()),
+            [count] => {
+                let count: usize = count.parse().map_err(|_| usage())?;
+                self.list_history(term, count)
+            }
+            _ => Err(usage())
+        }
+    }
+
+    // Write the in-memory history to the store, including the entries removed by -c and -d
+    fn write_history(&mut self) -> io::Result<()> {
+        let store = self.store.as_mut().ok_or_else(|| io::Error::other("history: no history file"))?;
+        let entries: Vec<HistoryEntry> = self.history.iter().map(|entry| self.settings.stored_entry(entry)).collect();
+
+        if self.cleared || store.len()? <= self.settings.max_size {
+            // The whole store fits into the in-memory history, replace it
+            store.clear()?;
+            for entry in &entries {
+                store.append(entry)?;
+            }
+        } else {
+            // The store holds more than the in-memory history, only remove what was deleted
+            // and add what it doesn't know yet
+            for entry in &self.deleted {
+                store.remove_entry(&self.settings.stored_entry(entry))?;
+            }
+
+            let stored = store.recent(entries.len())?;
+            for entry in entries.iter().filter(|entry| !stored.iter().any(|known| history::same_entry(known, entry))) {
+                store.append(entry)?;
+            }
+        }
+
+        self.deleted.clear();
+        self.cleared = false;
+        Ok(())
+    }
+
+    // Print the newest `count` entries numbered like `!n` refers to them
+    fn list_history(&self, term: &Term, count: usize) -> io::Result<()> {
+        for line in self.history_lines(count) {
+            term.write_line(&line)?;
+        }
+
+        Ok(())
+    }
+
+    fn history_lines(&self, count: usize) -> Vec<String> {
+        let skip = self.history.len().saturating_sub(count);
+
+        self.history.iter().enumerate().skip(skip)
+            .map(|(number, entry)| format!("{:>5}  {}", number + 1, entry.command))
+            .collect()
+    }
+
+    // fc [n | -n | string]: the entry to load into the editor, the previous one by default
+    fn fc_builtin(&self, args: &[String]) -> io::Result<String> {
+        let not_found = |spec: &str| io::Error::new(io::ErrorKind::NotFound, format!("fc: {}: no such history entry", spec));
+
+        let entry = match args {
+            [] => self.history.last().ok_or_else(|| not_found("-1"))?,
+            [spec] => {
+                let entry = match spec.parse::<i64>() {
+                    Ok(n) if n < 0 => self.history.len().checked_sub(n.unsigned_abs() as usize).and_then(|idx| self.history.get(idx)),
+                    Ok(n) if n > 0 => self.history.get(n as usize - 1),
+                    Ok(_) => None,
+                    Err(_) => self.history.iter().rev().find(|entry| entry.command.starts_with(spec.as_str()))
+                };
+                entry.ok_or_else(|| not_found(spec))?
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, FC_USAGE))
+        };
+
+        Ok(entry.command.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CliHistorySettings, HistoryStore, MemoryStore};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn session(commands: &[&str]) -> CliHistory {
+        let mut cli_history = CliHistory::new(CliHistorySettings::new());
+        for command in commands {
+            cli_history.value_add_history(command);
+        }
+        cli_history
+    }
+
+    fn commands(cli_history: &CliHistory) -> Vec<&str> {
+        cli_history.history.iter().map(|entry| entry.command.as_str()).collect()
+    }
+
+    #[test]
+    fn test_history_list_and_delete() {
+        let term = Term::stdout();
+        let mut cli_history = session(&["ls", "make", "git status"]);
+
+        assert_eq!(cli_history.history_lines(2), vec!["    2  make", "    3  git status"]);
+        assert_eq!(cli_history.history_lines(10).len(), 3);
+
+        cli_history.history_builtin(&term, &args("-d 2")).unwrap();
+        assert_eq!(commands(&cli_history), vec!["ls", "git status"]);
+        assert!(cli_history.history_builtin(&term, &args("-d 3")).is_err());
+        assert!(cli_history.history_builtin(&term, &args("-x")).is_err());
+
+        cli_history.history_builtin(&term, &args("-c")).unwrap();
+        assert!(cli_history.history.is_empty());
+    }
+
+    #[test]
+    fn test_history_write_and_read() {
+        let term = Term::stdout();
+        let mut cli_history = session(&[]);
+        cli_history.settings.set_keep_raw_in_memory();
+        cli_history.settings.add_redaction_rule(r"token=\S+", "token=[REDACTED]").unwrap();
+        cli_history.set_store(MemoryStore::new()).unwrap();
+
+        assert!(cli_history.history_builtin(&term, &args("-r")).is_ok());
+        cli_history.history.push(HistoryEntry::new("curl token=abc", ""));
+        cli_history.history.push(HistoryEntry::new("ls", ""));
+        cli_history.history_builtin(&term, &args("-w")).unwrap();
+
+        let stored = cli_history.store().unwrap().recent(10).unwrap();
+        let stored: Vec<&str> = stored.iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(stored, vec!["curl token=[REDACTED]", "ls"]);
+
+        cli_history.history.clear();
+        cli_history.history_builtin(&term, &args("-r")).unwrap();
+        assert_eq!(commands(&cli_history), vec!["curl token=[REDACTED]", "ls"]);
+    }
+
+    #[test]
+    fn test_history_write_keeps_larger_store() {
+        let term = Term::stdout();
+        let mut store = MemoryStore::new();
+        for command in ["old 1", "old 2", "old 3"] {
+            store.append(&HistoryEntry::new(command, "")).unwrap();
+        }
+
+        let mut cli_history = CliHistory::new(CliHistorySettings::builder().max_size(2).build());
+        cli_history.set_store(store).unwrap();
+        cli_history.history.push(HistoryEntry::new("new", ""));
+        cli_history.history_builtin(&term, &args("-w")).unwrap();
+
+        assert_eq!(cli_history.store().unwrap().len().unwrap(), 4);
+
+        // Deleted entries are removed from the store, the older ones stay
+        cli_history.history_builtin(&term, &args("-d 1")).unwrap();
+        cli_history.history_builtin(&term, &args("-w")).unwrap();
+        let stored = cli_history.store().unwrap().recent(10).unwrap();
+        let stored: Vec<&str> = stored.iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(stored, vec!["old 1", "old 3", "new"]);
+
+        cli_history.history_builtin(&term, &args("-c")).unwrap();
+        cli_history.history_builtin(&term, &args("-w")).unwrap();
+        assert!(cli_history.store().unwrap().is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_builtin_alias() {
+        let term = Term::stdout();
+        let mut cli_history = session(&["ls", "make"]);
+        cli_history.settings.set_history_builtins();
+        cli_history.settings.set_alias("h", "history");
+
+        assert!(matches!(cli_history.run_builtin(&term, "h -c"), Builtin::Done));
+        assert_eq!(commands(&cli_history), Vec::<&str>::new());
+        assert!(matches!(cli_history.run_builtin(&term, "hh"), Builtin::None));
+    }
+
+    #[test]
+    fn test_fc() {
+        let cli_history = session(&["ls -l", "make test", "git status"]);
+
+        assert_eq!(cli_history.fc_builtin(&[]).unwrap(), "git status");
+        assert_eq!(cli_history.fc_builtin(&args("1")).unwrap(), "ls -l");
+        assert_eq!(cli_history.fc_builtin(&args("-2")).unwrap(), "make test");
+        assert_eq!(cli_history.fc_builtin(&args("make")).unwrap(), "make test");
+        assert!(cli_history.fc_builtin(&args("0")).is_err());
+        assert!(cli_history.fc_builtin(&args("-4")).is_err());
+        assert!(cli_history.fc_builtin(&args("cargo")).is_err());
+        assert!(cli_history.fc_builtin(&args("1 2")).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use console::Term;
+
+use super::status::CommandStatus;
+use super::tokenize::tokenize;
+
+// Status of a line naming no registered command, like a shell's "command not found"
+const STATUS_UNKNOWN: i32 = 127;
+// Status of a line with wrong arguments or quoting
+const STATUS_USAGE: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ArgKind {
+    Required,
+    Optional,
+    Rest, // Takes all remaining arguments
+}
+
+struct ArgSpec {
+    name: String,
+    kind: ArgKind,
+}
+
+type Handler = Box<dyn Fn(&[String]) -> Option<i32>>;
+
+// A named command with its handler, argument specification, aliases and help text
+pub struct Command {
+    name: String,
+    aliases: Vec<String>,
+    help: String,
+    args: Vec<ArgSpec>,
+    handler: Handler,
+}
+
+impl Command {
+    // The handler receives the arguments following the command name
+    pub fn new<F, S>(name: &str, handler: F) -> Self
+    where F: Fn(&[String]) -> S + 'static, S: CommandStatus {
+        Command {
+            name: name.to_string(),
+            aliases: Vec::new(),
+            help: String::new(),
+            args: Vec::new(),
+            handler: Box::new(move |args: &[String]| handler(args).status())
+        }
+    }
+
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.aliases.push(alias.to_string());
+        self
+    }
+
+    pub fn help(mut self, help: &str) -> Self {
+        self.help = help.to_string();
+        self
+    }
+
+    // Argument which has to be given
+    pub fn arg(mut self, name: &str) -> Self {
+        self.args.push(ArgSpec { name: name.to_string(), kind: ArgKind::Required });
+        self
+    }
+
+    // Argument which may be left out, only followed by other optional ones
+    pub fn optional_arg(mut self, name: &str) -> Self {
+        self.args.push(ArgSpec { name: name.to_string(), kind: ArgKind::Optional });
+        self
+    }
+
+    // Any number of trailing arguments
+    pub fn rest_args(mut self, name: &str) -> Self {
+        self.args.push(ArgSpec { name: name.to_string(), kind: ArgKind::Rest });
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Synopsis like "copy <from> <to> [mode]"
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.clone();
+
+        for arg in &self.args {
+            usage.push_str(&match arg.kind {
+                ArgKind::Required => format!(" <{}>", arg.name),
+                ArgKind::Optional => format!(" [{}]", arg.name),
+                ArgKind::Rest => format!(" [{}...]", arg.name)
+            });
+        }
+
+        usage
+    }
+
+    fn accepts(&self, count: usize) -> bool {
+        let required = self.args.iter().filter(|arg| arg.kind == ArgKind::Required).count();
+        let unlimited = self.args.iter().any(|arg| arg.kind == ArgKind::Rest);
+
+        count >= required && (unlimited || count <= self.args.len())
+    }
+
+    fn is_called(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+// Commands the navigator dispatches entered lines to, by the first word of the line
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+    builtin_help: bool,
+}
+
+impl CommandRegistry {
+    // Registry with the built-in `help` command
+    pub fn new() -> Self {
+        CommandRegistry {
+            commands: Vec::new(),
+            builtin_help: true
+        }
+    }
+
+    // Leave out the built-in `help` command, e.g. to register an own one
+    pub fn without_help(mut self) -> Self {
+        self.builtin_help = false;
+        self
+    }
+
+    // Commands registered later take precedence over earlier ones with the same name
+    pub fn register(&mut self, command: Command) -> &mut Self {
+        self.commands.insert(0, command);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.is_called(name))
+    }
+
+    // Names and aliases of all commands, sorted, used for completion
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.commands.iter()
+            .flat_map(|command| std::iter::once(&command.name).chain(&command.aliases))
+            .cloned()
+            .collect();
+
+        if self.builtin_help {
+            names.push("help".to_string());
+        }
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // Overview of all commands, or usage and help of a single one
+    pub fn help_text(&self, name: Option<&str>) -> String {
+        if let Some(name) = name {
+            return match self.get(name) {
+                Some(command) => {
+                    let mut text = format!("usage: {}", command.usage());
+                    if !command.aliases.is_empty() {
+                        text.push_str(&format!("\naliases: {}", command.aliases.join(", ")));
+                    }
+                    if !command.help.is_empty() {
+                        text.push_str(&format!("\n{}", command.help));
+                    }
+                    text
+                }
+                None => self.unknown(name)
+            };
+        }
+
+        let mut commands: Vec<&Command> = self.commands.iter().collect();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let width = commands.iter().map(|command| command.name.len()).max().unwrap_or(0).max(4);
+        let mut lines: Vec<String> = commands.iter()
+            .map(|command| format!("  {:width$}  {}", command.name, command.help, width = width).trim_end().to_string())
+            .collect();
+
+        if self.builtin_help {
+            lines.push(format!("  {:width$}  Show this overview or the usage of a command", "help", width = width));
+        }
+
+        format!("Commands:\n{}", lines.join("\n"))
+    }
+
+    fn unknown(&self, name: &str) -> String {
+        match self.suggest(name) {
+            Some(suggestion) => format!("unknown command `{}`, did you mean `{}`?", name, suggestion),
+            None => format!("unknown command `{}`", name)
+        }
+    }
+
+    // Closest command name within a small edit distance
+    fn suggest(&self, name: &str) -> Option<String> {
+        let max_distance = (name.chars().count() / 2).clamp(1, 3);
+
+        self.names().into_iter()
+            .map(|candidate| (levenshtein(name, &candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+
+    // Run the command named by the first word of `line`, errors are reported on stderr
+    pub fn dispatch(&self, line: &str) -> Option<i32> {
+        let out = Term::stdout();
+        let err = Term::stderr();
+
+        let args = match tokenize(line) {
+            Ok(args) => args,
+            Err(error) => {
+                let _ = err.write_line(&error.to_string());
+                return Some(STATUS_USAGE);
+            }
+        };
+
+        let (name, args) = args.split_first()?; // Blank line
+
+        match self.get(name) {
+            Some(command) if command.accepts(args.len()) => (command.handler)(args),
+            Some(command) => {
+                let _ = err.write_line(&format!("usage: {}", command.usage()));
+                Some(STATUS_USAGE)
+            }
+            None if self.builtin_help && name == "help" => {
+                let _ = out.write_line(&self.help_text(args.first().map(String::as_str)));
+                Some(0)
+            }
+            None => {
+                let _ = err.write_line(&self.unknown(name));
+                Some(STATUS_UNKNOWN)
+            }
+        }
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Number of single char edits turning `a` into `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("help", "help"), 0);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut registry = CommandRegistry::new();
+
+        let record = Rc::clone(&seen);
+        registry.register(Command::new("copy", move |args: &[String]| record.borrow_mut().extend_from_slice(args))
+            .alias("cp")
+            .help("Copy a file")
+            .arg("from")
+            .arg("to")
+            .optional_arg("mode"));
+        registry.register(Command::new("status", |_: &[String]| 3));
+
+        assert_eq!(registry.dispatch("cp a 'b c'"), None);
+        assert_eq!(*seen.borrow(), vec!["a", "b c"]);
+        assert_eq!(registry.dispatch("status"), Some(3));
+        assert_eq!(registry.dispatch("copy a"), Some(STATUS_USAGE));
+        assert_eq!(registry.dispatch("copy a b c d"), Some(STATUS_USAGE));
+        assert_eq!(registry.dispatch("stats"), Some(STATUS_UNKNOWN));
+
+        assert_eq!(registry.suggest("stats"), Some("status".to_string()));
+        assert_eq!(registry.suggest("cpoy"), Some("copy".to_string()));
+        assert_eq!(registry.suggest("xyz"), None);
+        assert_eq!(registry.names(), vec!["copy", "cp", "help", "status"]);
+        assert_eq!(registry.get("cp").unwrap().usage(), "copy <from> <to> [mode]");
+    }
+}
@@ -0,0 +1,454 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use super::keymap::{parse_key, EditAction, EditMode};
+use super::redact::REDACTED;
+use super::CliHistorySettings;
+
+const ENV_PREFIX: &str = "CLIHISTORY_";
+
+// Environment variable pointing to the configuration file used by CliHistorySettings::load()
+pub const CONFIG_ENV: &str = "CLIHISTORY_CONFIG";
+
+const ENV_KEYS: [&str; 24] = [
+    "CONFIG",
+    "LABEL",
+    "RIGHT_LABEL",
+    "MAX_SIZE",
+    "MAX_SIZE_LOG_FILE",
+    "HISTORY_PATH",
+    "SHARE_HISTORY",
+    "IGNORE_DUPS",
+    "ERASE_DUPS",
+    "IGNORE_SPACE",
+    "IGNORE_PATTERNS",
+    "REDACT_SECRETS",
+    "HISTORY_EXPANSION",
+    "CONFIRM_EXPANSION",
+    "HISTORY_BUILTINS",
+    "ALIAS_BUILTINS",
+    "VARIABLE_EXPANSION",
+    "DIE_ON_EXIT",
+    "EXIT_WORDS",
+    "EDIT_MODE",
+    "MAX_PASTE_SIZE",
+    "KEEP_PASTE_NEWLINES",
+    "OSC52_CLIPBOARD",
+    "KEYBINDINGS",
+];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    // The configuration file couldn't be read
+    Io { path: PathBuf, source: io::Error },
+    // The configuration is no valid TOML or contains unknown keys, `path` is None for a string
+    Parse { path: Option<PathBuf>, source: toml::de::Error },
+    // A key holds a value which can't be used
+    InvalidValue { key: String, value: String, reason: String },
+    // An environment variable with the CLIHISTORY_ prefix is not known
+    UnknownEnv { name: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "failed to read {}: {}", path.display(), source),
+            ConfigError::Parse { path: Some(path), source } => write!(f, "invalid configuration in {}: {}", path.display(), source),
+            ConfigError::Parse { path: None, source } => write!(f, "invalid configuration: {}", source),
+            ConfigError::InvalidValue { key, value, reason } => write!(f, "invalid value `{}` for `{}`: {}", value, key, reason),
+            ConfigError::UnknownEnv { name } => {
+                let known: Vec<String> = ENV_KEYS.iter().map(|key| format!("{}{}", ENV_PREFIX, key)).collect();
+                write!(f, "unknown environment variable `{}`, expected one of: {}", name, known.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            _ => None
+        }
+    }
+}
+
+// Layout of the TOML configuration file
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    label: Option<String>,
+    right_label: Option<String>,
+    max_size: Option<usize>,
+    max_size_log_file: Option<usize>,
+    history_path: Option<String>,
+    share_history: Option<bool>,
+    ignore_dups: Option<bool>,
+    erase_dups: Option<bool>,
+    ignore_space: Option<bool>,
+    ignore_patterns: Option<Vec<String>>,
+    redact_secrets: Option<bool>,
+    redact_patterns: Option<Vec<String>>, // Matches are replaced with [REDACTED]
+    history_expansion: Option<bool>,
+    confirm_expansion: Option<bool>,
+    history_builtins: Option<bool>,
+    alias_builtins: Option<bool>,
+    variable_expansion: Option<bool>,
+    aliases: Option<BTreeMap<String, String>>,
+    variables: Option<BTreeMap<String, String>>,
+    die_on_exit: Option<bool>,
+    exit_words: Option<Vec<String>>,
+    edit_mode: Option<String>,
+    max_paste_size: Option<usize>,
+    keep_paste_newlines: Option<bool>,
+    osc52_clipboard: Option<bool>,
+    keybindings: Option<BTreeMap<String, String>>,
+}
+
+// Comma separated list of an environment variable: "exit,quit"
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn invalid(key: &str, value: &str, reason: String) -> ConfigError {
+    ConfigError::InvalidValue { 
+        key: key.to_string(), 
+        value: value.to_string(), 
+        reason 
+    }
+}
+
+fn parse_number(key: &str, value: &str) -> Result<usize, ConfigError> {
+    value.trim().parse().map_err(|err| invalid(key, value, format!("{}", err)))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ConfigError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(invalid(key, value, "expected true or false".to_string()))
+    }
+}
+
+impl CliHistorySettings {
+    // Default settings overridden by the configuration file and the environment.
+    // The file is taken from `path` or, if None, from $CLIHISTORY_CONFIG when set.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut settings = CliHistorySettings::new();
+        let env_path = std::env::var_os(CONFIG_ENV).map(PathBuf::from);
+
+        if let Some(path) = path.map(Path::to_path_buf).or(env_path) {
+            settings.apply_config_file(&path)?;
+        }
+
+        settings.apply_env()?;
+        Ok(settings)
+    }
+
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let mut settings = CliHistorySettings::new();
+        settings.apply_config_file(path)?;
+        Ok(settings)
+    }
+
+    // Override the current settings with the ones found in a TOML file
+    pub fn apply_config_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Io { 
+            path: path.to_path_buf(), 
+            source 
+        })?;
+
+        self.apply_config_str(&content).map_err(|err| match err {
+            ConfigError::Parse { source, .. } => ConfigError::Parse { path: Some(path.to_path_buf()), source },
+            err => err
+        })
+    }
+
+    pub fn apply_config_str(&mut self, content: &str) -> Result<(), ConfigError> {
+        let config: ConfigFile = toml::from_str(content).map_err(|source| ConfigError::Parse { 
+            path: None, 
+            source 
+        })?;
+
+        // Nothing is changed unless the whole file can be applied
+        let mut settings = self.clone();
+        settings.apply_config(config)?;
+        *self = settings;
+        Ok(())
+    }
+
+    fn apply_config(&mut self, config: ConfigFile) -> Result<(), ConfigError> {
+
+        if let Some(label) = config.label {
+            self.set_label(label);
+        }
+        if let Some(label) = config.right_label {
+            self.set_right_label(label);
+        }
+        if let Some(max_size) = config.max_size {
+            self.max_size = max_size;
+        }
+        if let Some(max_size) = config.max_size_log_file {
+            self.max_size_log_file = max_size;
+        }
+        if let Some(path) = config.history_path {
+            self.log_file_path = path;
+        }
+        if let Some(share) = config.share_history {
+            self.share_history = share;
+        }
+        if let Some(ignore) = config.ignore_dups {
+            self.ignore_dups = ignore;
+        }
+        if let Some(erase) = config.erase_dups {
+            self.erase_dups = erase;
+        }
+        if let Some(ignore) = config.ignore_space {
+            self.ignore_space = ignore;
+        }
+        if let Some(patterns) = config.ignore_patterns {
+            self.ignore_patterns = patterns;
+        }
+        if let Some(redact) = config.redact_secrets {
+            self.redact_secrets = redact;
+        }
+        if let Some(expand) = config.history_expansion {
+            self.history_expansion = expand;
+        }
+        if let Some(confirm) = config.confirm_expansion {
+            self.confirm_expansion = confirm;
+        }
+        if let Some(enable) = config.history_builtins {
+            self.history_builtins = enable;
+        }
+        if let Some(enable) = config.alias_builtins {
+            self.alias_builtins = enable;
+        }
+        if let Some(expand) = config.variable_expansion {
+            self.variable_expansion = expand;
+        }
+        self.aliases.extend(config.aliases.unwrap_or_default());
+        self.variables.extend(config.variables.unwrap_or_default());
+        for pattern in config.redact_patterns.unwrap_or_default() {
+            self.add_redaction_rule(&pattern, REDACTED)
+                .map_err(|err| invalid("redact_patterns", &pattern, err.to_string()))?;
+        }
+        if let Some(die_on_exit) = config.die_on_exit {
+            self.die_on_exit = die_on_exit;
+        }
+        if let Some(words) = config.exit_words {
+            self.exit_words = words;
+        }
+        if let Some(mode) = config.edit_mode {
+            self.edit_mode = mode.parse().map_err(|reason| invalid("edit_mode", &mode, reason))?;
+        }
+        if let Some(max_size) = config.max_paste_size {
+            self.max_paste_size = max_size;
+        }
+        if let Some(keep) = config.keep_paste_newlines {
+            self.keep_paste_newlines = keep;
+        }
+        if let Some(enable) = config.osc52_clipboard {
+            self.osc52_clipboard = enable;
+        }
+        for (key, action) in config.keybindings.unwrap_or_default() {
+            self.apply_binding(&format!("keybindings.\"{}\"", key), &key, &action)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_binding(&mut self, config_key: &str, key: &str, action: &str) -> Result<(), ConfigError> {
+        let parsed_key = parse_key(key).map_err(|reason| invalid(config_key, key, reason))?;
+        let parsed_action: EditAction = action.parse().map_err(|reason| invalid(config_key, action, reason))?;
+
+        self.bind_key(parsed_key, parsed_action);
+        Ok(())
+    }
+
+    // Override the current settings with CLIHISTORY_* environment variables
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        self.apply_env_vars(std::env::vars_os())
+    }
+
+    // Nothing is changed unless all variables can be applied
+    fn apply_env_vars<I, K, V>(&mut self, vars: I) -> Result<(), ConfigError>
+    where I: IntoIterator<Item = (K, V)>, K: Into<OsString>, V: Into<OsString> {
+        let mut settings = self.clone();
+
+        for (name, value) in vars {
+            let name: OsString = name.into();
+            if !name.as_encoded_bytes().starts_with(ENV_PREFIX.as_bytes()) {
+                // Other variables may hold anything, even invalid UTF-8
+                continue;
+            }
+
+            let name = name.into_string().map_err(|name| ConfigError::UnknownEnv { name: name.to_string_lossy().into_owned() })?;
+            let value = value.into().into_string().map_err(|value| {
+                invalid(&name, &value.to_string_lossy(), "not valid UTF-8".to_string())
+            })?;
+
+            settings.apply_env_var(name, value)?;
+        }
+
+        *self = settings;
+        Ok(())
+    }
+
+    fn apply_env_var(&mut self, name: String, value: String) -> Result<(), ConfigError> {
+        let key = match name.strip_prefix(ENV_PREFIX) {
+            Some(key) => key,
+            None => return Ok(())
+        };
+
+        match key {
+            "CONFIG" => {}, // Handled by load()
+            "LABEL" => self.set_label(value),
+            "RIGHT_LABEL" => self.set_right_label(value),
+            "MAX_SIZE" => self.max_size = parse_number(&name, &value)?,
+            "MAX_SIZE_LOG_FILE" => self.max_size_log_file = parse_number(&name, &value)?,
+            "HISTORY_PATH" => self.log_file_path = value,
+            "SHARE_HISTORY" => self.share_history = parse_bool(&name, &value)?,
+            "IGNORE_DUPS" => self.ignore_dups = parse_bool(&name, &value)?,
+            "ERASE_DUPS" => self.erase_dups = parse_bool(&name, &value)?,
+            "IGNORE_SPACE" => self.ignore_space = parse_bool(&name, &value)?,
+            "IGNORE_PATTERNS" => self.ignore_patterns = split_list(&value),
+            "REDACT_SECRETS" => self.redact_secrets = parse_bool(&name, &value)?,
+            "HISTORY_EXPANSION" => self.history_expansion = parse_bool(&name, &value)?,
+            "CONFIRM_EXPANSION" => self.confirm_expansion = parse_bool(&name, &value)?,
+            "HISTORY_BUILTINS" => self.history_builtins = parse_bool(&name, &value)?,
+            "ALIAS_BUILTINS" => self.alias_builtins = parse_bool(&name, &value)?,
+            "VARIABLE_EXPANSION" => self.variable_expansion = parse_bool(&name, &value)?,
+            "DIE_ON_EXIT" => self.die_on_exit = parse_bool(&name, &value)?,
+            "EXIT_WORDS" => self.exit_words = split_list(&value), // "exit,quit"
+            "EDIT_MODE" => {
+                self.edit_mode = value.parse::<EditMode>().map_err(|reason| invalid(&name, &value, reason))?;
+            }
+            "MAX_PASTE_SIZE" => self.max_paste_size = parse_number(&name, &value)?,
+            "KEEP_PASTE_NEWLINES" => self.keep_paste_newlines = parse_bool(&name, &value)?,
+            "OSC52_CLIPBOARD" => self.osc52_clipboard = parse_bool(&name, &value)?,
+            "KEYBINDINGS" => {
+                // Comma separated list: "ctrl-p=history-up,ctrl-n=history-down"
+                for binding in value.split(',').map(str::trim).filter(|b| !b.is_empty()) {
+                    let (key, action) = binding.split_once('=')
+                        .ok_or_else(|| invalid(&name, binding, "expected <key>=<action>".to_string()))?;
+                    self.apply_binding(&name, key.trim(), action.trim())?;
+                }
+            }
+            _ => return Err(ConfigError::UnknownEnv { name })
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use console::Key;
+
+    #[test]
+    fn test_config_str() {
+        let mut settings = CliHistorySettings::new();
+        settings.apply_config_str(r#"
+            label = "> "
+            max_size = 42
+            exit_words = ["quit", "bye"]
+            edit_mode = "basic"
+
+            [keybindings]
+            "ctrl-p" = "history-up"
+            "Alt-B" = "move-word-left"
+
+            [aliases]
+            ll = "list -l"
+        "#).unwrap();
+
+        assert_eq!(settings.max_size, 42);
+        assert_eq!(settings.exit_words, vec!["quit", "bye"]);
+        assert_eq!(settings.edit_mode, EditMode::Basic);
+        assert_eq!(settings.expand_command("ll src"), "list -l src");
+        assert_eq!(settings.key_bindings().get(&Key::Char('\x10')), Some(EditAction::HistoryUp));
+        assert_eq!(settings.key_bindings().get(&Key::UnknownEscSeq(vec!['B'])), Some(EditAction::MoveWordLeft));
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let mut settings = CliHistorySettings::new();
+
+        let err = settings.apply_config_str("max_sise = 1").unwrap_err();
+        assert!(err.to_string().contains("unknown field `max_sise`"));
+        assert!(err.to_string().starts_with("invalid configuration: "));
+
+        let err = settings.apply_config_str("redact_patterns = [\"(unclosed\"]").unwrap_err();
+        assert!(err.to_string().contains("redact_patterns"));
+
+        let err = settings.apply_config_str("[keybindings]\n\"ctrl-p\" = \"fly\"").unwrap_err();
+        assert!(err.to_string().contains("unknown action `fly`"));
+
+        let err = settings.apply_env_vars(vec![("CLIHISTORY_MAX_SIZE".to_string(), "many".to_string())]).unwrap_err();
+        assert!(err.to_string().contains("CLIHISTORY_MAX_SIZE"));
+
+        let err = settings.apply_env_vars(vec![("CLIHISTORY_COLOUR".to_string(), "red".to_string())]).unwrap_err();
+        assert!(matches!(err, ConfigError::UnknownEnv { .. }));
+
+        // A failing variable leaves everything as it was
+        let err = settings.apply_env_vars(vec![("CLIHISTORY_MAX_SIZE", "7"), ("CLIHISTORY_DIE_ON_EXIT", "maybe")]).unwrap_err();
+        assert!(err.to_string().contains("CLIHISTORY_DIE_ON_EXIT"));
+        assert_eq!(settings.max_size, CliHistorySettings::new().max_size);
+
+        let err = settings.apply_config_str("max_size = 7\nedit_mode = \"vi\"").unwrap_err();
+        assert!(err.to_string().contains("edit_mode"));
+        assert_eq!(settings.max_size, CliHistorySettings::new().max_size);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_env_not_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut settings = CliHistorySettings::new();
+        let garbage = || OsString::from_vec(vec![b'x', 0xff]);
+
+        settings.apply_env_vars(vec![(garbage(), garbage()), (OsString::from("CLIHISTORY_MAX_SIZE"), OsString::from("7"))]).unwrap();
+        assert_eq!(settings.max_size, 7);
+
+        let err = settings.apply_env_vars(vec![(OsString::from("CLIHISTORY_LABEL"), garbage())]).unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidValue { .. }));
+    }
+}
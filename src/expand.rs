@@ -0,0 +1,224 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::fmt;
+
+use super::history::HistoryEntry;
+
+// Why a line couldn't be expanded, the line is neither run nor recorded then
+#[derive(Debug, PartialEq)]
+pub(crate) enum ExpansionError {
+    EventNotFound(String),
+    BadWordSpecifier(String),
+    SubstitutionFailed,
+}
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpansionError::EventNotFound(event) => write!(f, "{}: event not found", event),
+            ExpansionError::BadWordSpecifier(spec) => write!(f, "{}: bad word specifier", spec),
+            ExpansionError::SubstitutionFailed => write!(f, "substitution failed")
+        }
+    }
+}
+
+// Chars ending an event name like `!cargo`
+fn ends_event(c: char) -> bool {
+    c.is_whitespace() || c == ':'
+}
+
+// Bash style history expansion of `line` against `history` (oldest first).
+// Returns None if the line contains nothing to expand.
+pub(crate) fn expand_history(line: &str, history: &[HistoryEntry]) -> Result<Option<String>, ExpansionError> {
+    if let Some(rest) = line.strip_prefix('^') {
+        return quick_substitution(rest, history).map(Some);
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut quote: Option<char> = None; // Nothing is expanded within single quotes
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        // Like bash, an escaped char is left alone and keeps its backslash
+        if c == '\\' && quote != Some('\'') && let Some(&escaped) = chars.get(i + 1) {
+            out.push(c);
+            out.push(escaped);
+            i += 2;
+            continue;
+        }
+
+        match quote {
+            None if c == '\'' || c == '"' => quote = Some(c),
+            Some(open) if open == c => quote = None,
+            _ => {}
+        }
+
+        let next = chars.get(i + 1).copied();
+        let closes_quote = |n: char| quote == Some('"') && n == '"';
+        if c != '!' || quote == Some('\'') || next.is_none_or(|n| n.is_whitespace() || n == '=' || n == '(' || closes_quote(n)) {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        i += 1;
+
+        // Shorthands for words of the previous command
+        if let Some(spec @ ('$' | '*')) = next {
+            i += 1;
+            let previous = event(history, 1).ok_or_else(|| ExpansionError::EventNotFound(format!("!{}", spec)))?;
+            out.push_str(&select_words(&previous.command, &spec.to_string())?);
+            continue;
+        }
+
+        let entry = match next {
+            Some('!') => {
+                i += 1;
+                event(history, 1)
+            }
+            Some('?') => {
+                // !?string? - the newest command containing string
+                i += 1;
+                let end = chars[i..].iter().position(|&c| c == '?').map_or(chars.len(), |pos| i + pos);
+                let needle: String = chars[i..end].iter().collect();
+                i = (end + 1).min(chars.len());
+                history.iter().rev().find(|entry| entry.command.contains(&needle))
+            }
+            Some(n) if n.is_ascii_digit() || n == '-' => {
+                let end = chars[i + 1..].iter().position(|c| !c.is_ascii_digit()).map_or(chars.len(), |pos| i + 1 + pos);
+                let number: String = chars[i..end].iter().collect();
+                i = end;
+
+                match number.parse::<i64>() {
+                    Ok(n) if n < 0 => event(history, n.unsigned_abs() as usize),
+                    Ok(n) if n > 0 => history.get(n as usize - 1),
+                    _ => None
+                }
+            }
+            _ => {
+                // !string - the newest command starting with string
+                let end = chars[i..].iter().position(|&c| ends_event(c)).map_or(chars.len(), |pos| i + pos);
+                let prefix: String = chars[i..end].iter().collect();
+                i = end;
+                history.iter().rev().find(|entry| entry.command.starts_with(&prefix))
+            }
+        };
+
+        let designator: String = chars[start..i].iter().collect();
+        let entry = entry.ok_or(ExpansionError::EventNotFound(designator))?;
+
+        // Optional word designator: !!:1, !-2:$, !ls:*
+        if chars.get(i) == Some(&':') {
+            let end = chars[i + 1..].iter().position(|&c| c.is_whitespace()).map_or(chars.len(), |pos| i + 1 + pos);
+            let spec: String = chars[i + 1..end].iter().collect();
+            i = end;
+            out.push_str(&select_words(&entry.command, &spec)?);
+        } else {
+            out.push_str(&entry.command);
+        }
+
+    }
+
+    Ok((out != line).then_some(out))
+}
+
+// The `back`-th command counted from the newest one
+fn event(history: &[HistoryEntry], back: usize) -> Option<&HistoryEntry> {
+    history.len().checked_sub(back).and_then(|idx| history.get(idx))
+}
+
+// Words of `command` selected by `spec`: N, ^, $ or *
+fn select_words(command: &str, spec: &str) -> Result<String, ExpansionError> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let bad = || ExpansionError::BadWordSpecifier(format!(":{}", spec));
+
+    let word = match spec {
+        "*" => return Ok(words.iter().skip(1).copied().collect::<Vec<_>>().join(" ")),
+        "^" => words.get(1),
+        "$" => words.last(),
+        n => words.get(n.parse::<usize>().map_err(|_| bad())?)
+    };
+
+    word.map(|word| word.to_string()).ok_or_else(bad)
+}
+
+// ^old^new - the previous command with the first `old` replaced by `new`
+fn quick_substitution(rest: &str, history: &[HistoryEntry]) -> Result<String, ExpansionError> {
+    let mut parts = rest.splitn(3, '^');
+    let old = parts.next().unwrap_or_default();
+    let new = parts.next().unwrap_or_default();
+
+    let previous = event(history, 1).ok_or(ExpansionError::SubstitutionFailed)?;
+    if old.is_empty() || !previous.command.contains(old) {
+        return Err(ExpansionError::SubstitutionFailed);
+    }
+
+    Ok(previous.command.replacen(old, new, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(commands: &[&str]) -> Vec<HistoryEntry> {
+        commands.iter().map(|command| HistoryEntry::new(command, "")).collect()
+    }
+
+    #[test]
+    fn test_events() {
+        let history = history(&["cargo build", "ls -la src", "git commit -m 'fix'"]);
+        let expand = |line| expand_history(line, &history).unwrap();
+
+        assert_eq!(expand("sudo !!"), Some("sudo git commit -m 'fix'".to_string()));
+        assert_eq!(expand("!1"), Some("cargo build".to_string()));
+        assert_eq!(expand("!-2"), Some("ls -la src".to_string()));
+        assert_eq!(expand("!car --release"), Some("cargo build --release".to_string()));
+        assert_eq!(expand("!?-la?"), Some("ls -la src".to_string()));
+        assert_eq!(expand("echo hi"), None);
+        assert_eq!(expand("echo hi! '!!' \\!"), None);
+        assert_eq!(expand("echo \\!! !!"), Some("echo \\!! git commit -m 'fix'".to_string()));
+        assert_eq!(expand("echo \"it's !!\""), Some("echo \"it's git commit -m 'fix'\"".to_string()));
+        assert_eq!(expand("echo \"hi!\" '\"!!'"), None);
+    }
+
+    #[test]
+    fn test_words_and_substitution() {
+        let history = history(&["cp a.txt b.txt", "vim notes.md"]);
+        let expand = |line| expand_history(line, &history);
+
+        assert_eq!(expand("cat !$").unwrap(), Some("cat notes.md".to_string()));
+        assert_eq!(expand("rm !-2:*").unwrap(), Some("rm a.txt b.txt".to_string()));
+        assert_eq!(expand("echo !cp:2").unwrap(), Some("echo b.txt".to_string()));
+        assert_eq!(expand("^notes^todo").unwrap(), Some("vim todo.md".to_string()));
+
+        assert_eq!(expand("!nope"), Err(ExpansionError::EventNotFound("!nope".to_string())));
+        assert_eq!(expand("!!:7"), Err(ExpansionError::BadWordSpecifier(":7".to_string())));
+        assert_eq!(expand("^xyz^abc"), Err(ExpansionError::SubstitutionFailed));
+    }
+}
@@ -0,0 +1,461 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::history::HistoryEntry;
+
+// On-disk layout of the history file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    // One command per line, backslashes and newlines are escaped as \\ and \n
+    #[default]
+    Plain,
+    // One JSON object per line holding the command and its metadata
+    JsonLines,
+    // zsh EXTENDED_HISTORY lines ": <timestamp>:<duration>;<command>",
+    // newlines inside a command are continued with a trailing backslash,
+    // backslashes a command line ends with are doubled
+    ZshExtended,
+    // bash HISTTIMEFORMAT style "#<timestamp>" line followed by the command,
+    // a command runs up to the next timestamp line. Command lines looking
+    // like a timestamp get a leading backslash
+    BashTimestamps,
+}
+
+// Turn an entry into the text appended to the history file, including the final newline
+pub(crate) fn encode(format: LogFormat, entry: &HistoryEntry) -> String {
+    match format {
+        LogFormat::Plain => format!("{}\n", escape_plain(&entry.command)),
+        LogFormat::JsonLines => format!("{}\n", encode_json(entry)),
+        LogFormat::ZshExtended => {
+            let duration = entry.duration.map(|d| d.as_secs()).unwrap_or(0);
+            format!(": {}:{};{}\n", entry.unix_timestamp(), duration, escape_zsh(&entry.command))
+        }
+        LogFormat::BashTimestamps => format!("#{}\n{}\n", entry.unix_timestamp(), escape_bash(&entry.command))
+    }
+}
+
+// Read all entries of a history file. Metadata the format doesn't know about is left empty,
+// a missing timestamp is reported as UNIX_EPOCH. Lines which can't be decoded are skipped.
+pub(crate) fn decode(format: LogFormat, content: &str) -> Vec<HistoryEntry> {
+    match format {
+        LogFormat::Plain => content.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| stored_entry(&unescape_plain(line), 0))
+            .collect(),
+        LogFormat::JsonLines => content.lines()
+            .filter_map(decode_json)
+            .collect(),
+        LogFormat::ZshExtended => decode_zsh(content),
+        LogFormat::BashTimestamps => decode_bash(content)
+    }
+}
+
+fn stored_entry(command: &str, timestamp: u64) -> HistoryEntry {
+    HistoryEntry {
+        command: command.to_string(),
+        timestamp: UNIX_EPOCH + Duration::from_secs(timestamp),
+        duration: None,
+        exit_status: None,
+        cwd: None,
+        session_id: String::new(),
+    }
+}
+
+fn escape_plain(command: &str) -> String {
+    command.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_plain(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some(other) => {
+                // Not written by us, keep it as it is
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\')
+        }
+    }
+
+    out
+}
+
+// Number of backslashes `line` ends with
+fn trailing_backslashes(line: &str) -> usize {
+    line.bytes().rev().take_while(|b| *b == b'\\').count()
+}
+
+// Double the backslashes each line ends with, so only the single backslash
+// added in front of a newline leaves an odd number at the end of a line
+fn escape_zsh(command: &str) -> String {
+    let lines: Vec<String> = command.split('\n')
+        .map(|line| format!("{}{}", line, "\\".repeat(trailing_backslashes(line))))
+        .collect();
+
+    lines.join("\\\n")
+}
+
+// Undo escape_zsh() for a single line, returns whether the command continues on the next line
+fn unescape_zsh_line(line: &str) -> (&str, bool) {
+    let count = trailing_backslashes(line);
+    let continues = count % 2 == 1;
+
+    (&line[..line.len() - count + count / 2], continues)
+}
+
+fn decode_zsh(content: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let (first, mut continues) = unescape_zsh_line(line);
+        let mut command = first.to_string();
+
+        // An odd number of trailing backslashes continues the command on the next line
+        while continues {
+            match lines.next() {
+                Some(next) => {
+                    let (part, more) = unescape_zsh_line(next);
+                    command.push('\n');
+                    command.push_str(part);
+                    continues = more;
+                }
+                None => break
+            }
+        }
+
+        let entry = command.strip_prefix(": ")
+            .and_then(|rest| rest.split_once(';'))
+            .and_then(|(meta, cmd)| {
+                let (timestamp, duration) = meta.split_once(':')?;
+                let mut entry = stored_entry(cmd, timestamp.trim().parse().ok()?);
+                entry.duration = duration.trim().parse().ok().map(Duration::from_secs);
+                Some(entry)
+            });
+
+        match entry {
+            Some(entry) => entries.push(entry),
+            // Written without EXTENDED_HISTORY, just the command
+            None if !command.is_empty() => entries.push(stored_entry(&command, 0)),
+            None => {}
+        }
+    }
+
+    entries
+}
+
+fn bash_timestamp(line: &str) -> Option<u64> {
+    let digits = line.strip_prefix('#')?;
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    digits.parse().ok()
+}
+
+// A command line which would be read as timestamp line, possibly escaped already
+fn looks_like_timestamp(line: &str) -> bool {
+    bash_timestamp(line.trim_start_matches('\\')).is_some()
+}
+
+fn escape_bash(command: &str) -> String {
+    let lines: Vec<String> = command.split('\n')
+        .map(|line| if looks_like_timestamp(line) { format!("\\{}", line) } else { line.to_string() })
+        .collect();
+
+    lines.join("\n")
+}
+
+fn unescape_bash_line(line: &str) -> &str {
+    match line.strip_prefix('\\') {
+        Some(rest) if looks_like_timestamp(rest) => rest,
+        _ => line
+    }
+}
+
+fn decode_bash(content: &str) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    let mut timestamp: Option<u64> = None;
+    let mut continues = false; // The current entry may get more lines
+
+    for line in content.lines() {
+        if let Some(ts) = bash_timestamp(line) {
+            timestamp = Some(ts);
+            continues = false;
+            continue;
+        }
+
+        let line = unescape_bash_line(line);
+
+        match timestamp.take() {
+            Some(ts) => {
+                entries.push(stored_entry(line, ts));
+                continues = true;
+            }
+            None if continues => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.command.push('\n');
+                    entry.command.push_str(line);
+                }
+            }
+            // Lines without any timestamp are single commands
+            None if !line.is_empty() => entries.push(stored_entry(line, 0)),
+            None => {}
+        }
+    }
+
+    entries
+}
+
+// Quote a string as JSON string literal
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+fn encode_json(entry: &HistoryEntry) -> String {
+    let mut fields = vec![
+        format!("\"command\":{}", json_escape(&entry.command)),
+        format!("\"timestamp\":{}", entry.unix_timestamp()),
+    ];
+
+    if let Some(duration) = entry.duration {
+        fields.push(format!("\"duration_ms\":{}", duration.as_millis()));
+    }
+    if let Some(status) = entry.exit_status {
+        fields.push(format!("\"exit_status\":{}", status));
+    }
+    if let Some(ref cwd) = entry.cwd {
+        fields.push(format!("\"cwd\":{}", json_escape(&cwd.to_string_lossy())));
+    }
+    fields.push(format!("\"session\":{}", json_escape(&entry.session_id)));
+
+    format!("{{{}}}", fields.join(","))
+}
+
+// Values of the flat JSON objects we write
+enum JsonValue {
+    Str(String),
+    Num(i64),
+    Null,
+}
+
+// Minimal reader for the flat objects written by encode_json()
+struct JsonReader<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl JsonReader<'_> {
+    fn skip_ws(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        self.skip_ws();
+        (self.chars.next()? == expected).then_some(())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut out = String::new();
+
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(out),
+                '\\' => match self.chars.next()? {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'b' => out.push('\u{8}'),
+                    'f' => out.push('\u{c}'),
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?).unwrap_or('\u{fffd}'));
+                    }
+                    other => out.push(other)
+                },
+                c => out.push(c)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<JsonValue> {
+        self.skip_ws();
+
+        match *self.chars.peek()? {
+            '"' => self.string().map(JsonValue::Str),
+            'n' => {
+                let word: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                (word == "null").then_some(JsonValue::Null)
+            }
+            _ => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '-') {
+                    number.push(c);
+                }
+                number.parse().ok().map(JsonValue::Num)
+            }
+        }
+    }
+}
+
+fn decode_json(line: &str) -> Option<HistoryEntry> {
+    let mut reader = JsonReader { chars: line.chars().peekable() };
+    let mut entry = stored_entry("", 0);
+    let mut has_command = false;
+
+    reader.expect('{')?;
+    reader.skip_ws();
+    if reader.chars.next_if_eq(&'}').is_some() {
+        return None;
+    }
+
+    loop {
+        let key = reader.string()?;
+        reader.expect(':')?;
+        let value = reader.value()?;
+
+        match (key.as_str(), value) {
+            ("command", JsonValue::Str(command)) => {
+                entry.command = command;
+                has_command = true;
+            }
+            ("timestamp", JsonValue::Num(ts)) => entry.timestamp = UNIX_EPOCH + Duration::from_secs(ts.max(0) as u64),
+            ("duration_ms", JsonValue::Num(ms)) => entry.duration = Some(Duration::from_millis(ms.max(0) as u64)),
+            ("exit_status", JsonValue::Num(status)) => entry.exit_status = Some(status as i32),
+            ("cwd", JsonValue::Str(cwd)) => entry.cwd = Some(PathBuf::from(cwd)),
+            ("session", JsonValue::Str(session)) => entry.session_id = session,
+            _ => {} // Unknown or null values are ignored
+        }
+
+        reader.skip_ws();
+        match reader.chars.next()? {
+            ',' => continue,
+            '}' => break,
+            _ => return None
+        }
+    }
+
+    has_command.then_some(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(format: LogFormat, entries: &[HistoryEntry]) -> Vec<HistoryEntry> {
+        let content: String = entries.iter().map(|entry| encode(format, entry)).collect();
+        decode(format, &content)
+    }
+
+    fn sample(command: &str) -> HistoryEntry {
+        let mut entry = stored_entry(command, 1700000000);
+        entry.duration = Some(Duration::from_secs(3));
+        entry
+    }
+
+    #[test]
+    fn test_plain_escaping() {
+        let entries = [sample("echo a\\nb"), sample("for x in 1 2\ndo echo $x\ndone")];
+        let decoded = roundtrip(LogFormat::Plain, &entries);
+
+        assert_eq!(encode(LogFormat::Plain, &entries[0]), "echo a\\\\nb\n");
+        assert_eq!(decoded[0].command, entries[0].command);
+        assert_eq!(decoded[1].command, entries[1].command);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut entry = sample("say \"hi\"\n\tand \\ leave");
+        entry.exit_status = Some(-2);
+        entry.cwd = Some(PathBuf::from("/tmp"));
+        entry.session_id = "abc".to_string();
+
+        assert_eq!(roundtrip(LogFormat::JsonLines, &[entry.clone()]), vec![entry]);
+    }
+
+    #[test]
+    fn test_zsh_and_bash() {
+        let entries = [sample("ls -la"), sample("echo one\necho two"), sample("pwd")];
+
+        for format in [LogFormat::ZshExtended, LogFormat::BashTimestamps] {
+            let decoded = roundtrip(format, &entries);
+            let commands: Vec<&str> = decoded.iter().map(|entry| entry.command.as_str()).collect();
+
+            assert_eq!(commands, vec!["ls -la", "echo one\necho two", "pwd"]);
+            assert_eq!(decoded[1].unix_timestamp(), 1700000000);
+        }
+
+        assert_eq!(encode(LogFormat::ZshExtended, &entries[0]), ": 1700000000:3;ls -la\n");
+        assert_eq!(decode(LogFormat::ZshExtended, ": 1700000000:3;ls -la\n")[0].duration, Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_zsh_backslashes() {
+        let entries = [sample("echo a\\"), sample("ls"), sample("printf x\\\\\nand\\\n\\"), sample("a \\b")];
+        let decoded = roundtrip(LogFormat::ZshExtended, &entries);
+        let commands: Vec<&str> = decoded.iter().map(|entry| entry.command.as_str()).collect();
+
+        assert_eq!(commands, entries.iter().map(|entry| entry.command.as_str()).collect::<Vec<_>>());
+        assert_eq!(encode(LogFormat::ZshExtended, &entries[0]), ": 1700000000:3;echo a\\\\\n");
+    }
+
+    #[test]
+    fn test_bash_timestamp_lines() {
+        let entries = [sample("cat <<EOF\n#1234\n\\#99\nEOF"), sample("#42"), sample("ls")];
+        let decoded = roundtrip(LogFormat::BashTimestamps, &entries);
+        let commands: Vec<&str> = decoded.iter().map(|entry| entry.command.as_str()).collect();
+
+        assert_eq!(commands, vec!["cat <<EOF\n#1234\n\\#99\nEOF", "#42", "ls"]);
+    }
+}
@@ -0,0 +1,131 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::fmt;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// A single command of the history together with when and where it ran
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub command: String,
+    pub timestamp: SystemTime, // When the command was submitted
+    pub duration: Option<Duration>, // How long the callback took
+    pub exit_status: Option<i32>, // Status reported by the callback
+    pub cwd: Option<PathBuf>,
+    pub session_id: String, // Identifies the CliHistory the command was entered in
+}
+
+impl HistoryEntry {
+    pub fn new(command: &str, session_id: &str) -> Self {
+        HistoryEntry {
+            command: command.to_string(),
+            timestamp: SystemTime::now(),
+            duration: None,
+            exit_status: None,
+            cwd: std::env::current_dir().ok(),
+            session_id: session_id.to_string(),
+        }
+    }
+
+    // Seconds since the unix epoch
+    pub fn unix_timestamp(&self) -> u64 {
+        self.timestamp.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.command)
+    }
+}
+
+// Identifier unique enough to tell sessions writing the same history apart
+pub(crate) fn new_session_id() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    format!("{:x}-{:x}", process::id(), nanos)
+}
+
+// Whether `a` and `b` are the same entry, possibly read back from a store. Stores keep
+// the timestamp in seconds only. The session is compared when both entries carry one,
+// formats without it read entries back with an empty session id.
+pub(crate) fn same_entry(a: &HistoryEntry, b: &HistoryEntry) -> bool {
+    let same_session = a.session_id.is_empty() || b.session_id.is_empty() || a.session_id == b.session_id;
+    a.unix_timestamp() == b.unix_timestamp() && a.command == b.command && same_session
+}
+
+// Merge entries read from a shared history into `history`. Entries already known are
+// recognized by their timestamp (in seconds, as stored) and command. The result stays
+// ordered by timestamp and keeps the newest `max_size` entries.
+pub(crate) fn merge_shared(history: &mut Vec<HistoryEntry>, shared: Vec<HistoryEntry>, max_size: usize) -> usize {
+    let mut added = 0;
+
+    for entry in shared {
+        let known = history.iter().any(|known| same_entry(known, &entry));
+
+        if !known {
+            history.push(entry);
+            added += 1;
+        }
+    }
+
+    if added > 0 {
+        history.sort_by_key(|entry| entry.unix_timestamp());
+        let overflow = history.len().saturating_sub(max_size);
+        history.drain(..overflow);
+    }
+
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_at(command: &str, secs: u64, session_id: &str) -> HistoryEntry {
+        let mut entry = HistoryEntry::new(command, session_id);
+        entry.timestamp = UNIX_EPOCH + Duration::from_secs(secs);
+        entry
+    }
+
+    #[test]
+    fn test_merge_shared() {
+        let mut history = vec![entry_at("ls", 10, "a"), entry_at("make", 30, "a")];
+        let shared = vec![
+            entry_at("ls", 10, ""), // Our own entry read back from the file
+            entry_at("cd src", 20, "b"),
+            entry_at("git log", 40, "b"),
+        ];
+
+        assert_eq!(merge_shared(&mut history, shared, 3), 2);
+
+        let commands: Vec<&str> = history.iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(commands, vec!["cd src", "make", "git log"]);
+
+        // The same command run by another session within the same second
+        assert_eq!(merge_shared(&mut history, vec![entry_at("git log", 40, "a")], 4), 1);
+        assert_eq!(history.len(), 4);
+    }
+}
@@ -22,14 +22,15 @@
 * SOFTWARE.
 */
 
-use console::Key;
+use console::{Key, Term};
+
+use super::keymap::{EditAction, KeyBindings};
 
 // History navigation instructions
 #[derive(Debug, PartialEq)]
 pub enum KeyHandle {
     None,
     ArrowKeyUp,
-    ArrowKeyDown,
     EnterKey
 }
 
@@ -46,11 +47,10 @@ impl Hooks {
     }
 
     // Update the current arrow key state 
-    pub(crate) fn update(&mut self, key: Key) {
-        match key {
-            Key::ArrowUp => self.handle = KeyHandle::ArrowKeyUp,
-            Key::ArrowDown => self.handle = KeyHandle::ArrowKeyDown,
-            Key::Enter => self.handle = KeyHandle::EnterKey,
+    pub(crate) fn update(&mut self, key: &Key, bindings: &KeyBindings) {
+        match bindings.get(key) {
+            Some(EditAction::HistoryUp) => self.handle = KeyHandle::ArrowKeyUp,
+            Some(EditAction::Submit) => self.handle = KeyHandle::EnterKey,
             _ => self.handle = KeyHandle::None
         }
     }
@@ -59,23 +59,80 @@ impl Hooks {
         self.handle == KeyHandle::ArrowKeyUp
     }
 
-    pub(crate) fn is_arrow_down(&self) -> bool {
-        self.handle == KeyHandle::ArrowKeyDown
-    }
-
     pub(crate) fn is_enter(&self) -> bool {
         self.handle == KeyHandle::EnterKey
     }
 
     pub(crate) fn get_char(key: Key) -> Option<char> {
-        for i in 0..26 { 
-            // Iterate through the alphabet to determine the first pressed key
-            let c = (b'a' + i) as char;
-            if key == Key::Char(c) {
-                return Some(c)
+        // Every printable char is accepted, control chars are left to the editor
+        match key {
+            Key::Char(c) if !c.is_control() => Some(c),
+            _ => None
+        }
+    }
+
+    // Ask the terminal to wrap pasted text in \x1b[200~ .. \x1b[201~
+    pub(crate) fn enable_bracketed_paste(term: &Term) {
+        if term.is_term() {
+            let _ = term.write_str("\x1b[?2004h");
+        }
+    }
+
+    pub(crate) fn disable_bracketed_paste(term: &Term) {
+        if term.is_term() {
+            let _ = term.write_str("\x1b[?2004l");
+        }
+    }
+
+    // console reports \x1b[200~ as an unknown "\x1b[20" sequence followed by
+    // the chars '0' and '~', \x1b[201~ ends with '1' and '~' instead.
+    // Other keys start the same way, F9 sends \x1b[20~, so only the '~' of
+    // those is consumed and the next key is left alone.
+    fn read_paste_marker(term: &Term, key: &Key) -> Option<char> {
+        if *key != Key::UnknownEscSeq(vec!['[', '2', '0']) {
+            return None;
+        }
+
+        match term.read_key() {
+            Ok(Key::Char(c @ ('0' | '1'))) => match term.read_key() {
+                Ok(Key::Char('~')) => Some(c),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    pub(crate) fn is_paste_start(term: &Term, key: &Key) -> bool {
+        Hooks::read_paste_marker(term, key) == Some('0')
+    }
+
+    // Collect the pasted text up to the closing marker. Newlines are kept when 
+    // `keep_newlines` is set and replaced by spaces otherwise, chars beyond
+    // `max_size` are dropped.
+    pub(crate) fn read_paste(term: &Term, max_size: usize, keep_newlines: bool) -> String {
+        let mut pasted = String::new();
+        let mut count = 0;
+
+        while let Ok(key) = term.read_key() {
+            let c = match key {
+                Key::Enter if keep_newlines => '\n',
+                Key::Enter | Key::Tab => ' ',
+                Key::Char(c) if !c.is_control() => c,
+                ref esc @ Key::UnknownEscSeq(_) => {
+                    if Hooks::read_paste_marker(term, esc) == Some('1') {
+                        break;
+                    }
+                    continue;
+                }
+                _ => continue
+            };
+
+            if count < max_size {
+                pasted.push(c);
+                count += 1;
             }
         }
 
-        None
+        pasted
     }
 }
\ No newline at end of file
@@ -0,0 +1,256 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use console::Key;
+
+// Editing operations a key can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EditAction {
+    MoveLeft,
+    MoveRight,
+    MoveWordLeft,
+    MoveWordRight,
+    MoveHome,
+    MoveEnd,
+    DeleteBackward,
+    DeleteForward,
+    HistoryUp,
+    HistoryDown,
+    Submit,
+    Complete,
+    Undo,
+    Redo,
+    KillLine,
+    KillLineStart,
+    KillWordBackward,
+    KillWordForward,
+    Yank,
+    YankRotate,
+}
+
+impl EditAction {
+    const NAMES: [(&'static str, EditAction); 20] = [
+        ("move-left", EditAction::MoveLeft),
+        ("move-right", EditAction::MoveRight),
+        ("move-word-left", EditAction::MoveWordLeft),
+        ("move-word-right", EditAction::MoveWordRight),
+        ("move-home", EditAction::MoveHome),
+        ("move-end", EditAction::MoveEnd),
+        ("delete-backward", EditAction::DeleteBackward),
+        ("delete-forward", EditAction::DeleteForward),
+        ("history-up", EditAction::HistoryUp),
+        ("history-down", EditAction::HistoryDown),
+        ("submit", EditAction::Submit),
+        ("complete", EditAction::Complete),
+        ("undo", EditAction::Undo),
+        ("redo", EditAction::Redo),
+        ("kill-line", EditAction::KillLine),
+        ("kill-line-start", EditAction::KillLineStart),
+        ("kill-word-backward", EditAction::KillWordBackward),
+        ("kill-word-forward", EditAction::KillWordForward),
+        ("yank", EditAction::Yank),
+        ("yank-rotate", EditAction::YankRotate),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        EditAction::NAMES.iter()
+            .find(|(_, action)| action == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        EditAction::NAMES.iter().map(|(name, _)| *name)
+    }
+
+    // Actions moving the removed text to the kill ring
+    pub fn is_kill(self) -> bool {
+        matches!(self, EditAction::KillLine | EditAction::KillLineStart | EditAction::KillWordBackward | EditAction::KillWordForward)
+    }
+}
+
+impl FromStr for EditAction {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        EditAction::NAMES.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, action)| *action)
+            .ok_or_else(|| format!("unknown action `{}`, expected one of: {}", name, EditAction::names().collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl fmt::Display for EditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Set of default key bindings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EditMode {
+    // Arrow keys plus the common emacs/readline control keys
+    #[default]
+    Emacs,
+    // Arrow, Home/End, Backspace/Delete and Enter keys only
+    Basic,
+}
+
+impl FromStr for EditMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "emacs" => Ok(EditMode::Emacs),
+            "basic" => Ok(EditMode::Basic),
+            _ => Err(format!("unknown edit mode `{}`, expected one of: emacs, basic", name))
+        }
+    }
+}
+
+// Parse a key description like "ctrl-p", "alt-b", "up" or "x".
+// Keep in mind console already reports ctrl-a/ctrl-e as Home/End,
+// ctrl-h as Backspace, ctrl-i as Tab and ctrl-j/ctrl-m as Enter.
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    let lower = name.to_ascii_lowercase();
+
+    let key = match lower.as_str() {
+        "up" => Key::ArrowUp,
+        "down" => Key::ArrowDown,
+        "left" => Key::ArrowLeft,
+        "right" => Key::ArrowRight,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "enter" | "return" => Key::Enter,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Del,
+        "tab" => Key::Tab,
+        "escape" | "esc" => Key::Escape,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "space" => Key::Char(' '),
+        _ => {
+            // The prefix is matched case-insensitively, the char after alt- keeps its case
+            let alt = ["alt-", "meta-"].iter().find(|prefix| lower.starts_with(*prefix)).map(|prefix| &name[prefix.len()..]);
+
+            if let Some(c) = single_char(alt) {
+                Key::UnknownEscSeq(vec![c])
+            } else if let Some(c) = single_char(lower.strip_prefix("ctrl-")) {
+                match c {
+                    'a' => Key::Home,
+                    'e' => Key::End,
+                    'h' => Key::Backspace,
+                    'i' => Key::Tab,
+                    'j' | 'm' => Key::Enter,
+                    '@'..='_' | 'a'..='z' => Key::Char(((c.to_ascii_uppercase() as u8) & 0x1f) as char),
+                    _ => return Err(format!("unknown key `{}`", name))
+                }
+            } else if let Some(c) = single_char(Some(name)) {
+                Key::Char(c)
+            } else {
+                return Err(format!("unknown key `{}`", name));
+            }
+        }
+    };
+
+    Ok(key)
+}
+
+fn single_char(s: Option<&str>) -> Option<char> {
+    let mut chars = s?.chars();
+    let c = chars.next()?;
+
+    if chars.next().is_none() { Some(c) } else { None }
+}
+
+// Keys bound to editing actions, on top of the defaults of an edit mode
+#[derive(Clone, Debug, Default)]
+pub struct KeyBindings {
+    bindings: HashMap<Key, EditAction>,
+}
+
+impl KeyBindings {
+    pub fn new() -> Self {
+        KeyBindings { 
+            bindings: HashMap::new() 
+        }
+    }
+
+    pub fn for_mode(mode: EditMode) -> Self {
+        let mut bindings = KeyBindings::new();
+
+        bindings.bind(Key::ArrowLeft, EditAction::MoveLeft);
+        bindings.bind(Key::ArrowRight, EditAction::MoveRight);
+        bindings.bind(Key::Home, EditAction::MoveHome);
+        bindings.bind(Key::End, EditAction::MoveEnd);
+        bindings.bind(Key::Backspace, EditAction::DeleteBackward);
+        bindings.bind(Key::Del, EditAction::DeleteForward);
+        bindings.bind(Key::ArrowUp, EditAction::HistoryUp);
+        bindings.bind(Key::ArrowDown, EditAction::HistoryDown);
+        bindings.bind(Key::Enter, EditAction::Submit);
+        bindings.bind(Key::Tab, EditAction::Complete);
+
+        if mode == EditMode::Emacs {
+            bindings.bind(Key::Char('\x02'), EditAction::MoveLeft); // ctrl-b
+            bindings.bind(Key::Char('\x06'), EditAction::MoveRight); // ctrl-f
+            bindings.bind(Key::UnknownEscSeq(vec!['b']), EditAction::MoveWordLeft); // alt-b
+            bindings.bind(Key::UnknownEscSeq(vec!['f']), EditAction::MoveWordRight); // alt-f
+            bindings.bind(Key::Char('\x04'), EditAction::DeleteForward); // ctrl-d
+            bindings.bind(Key::Char('\x10'), EditAction::HistoryUp); // ctrl-p
+            bindings.bind(Key::Char('\x0e'), EditAction::HistoryDown); // ctrl-n
+            bindings.bind(Key::Char('\x1f'), EditAction::Undo); // ctrl-_
+            bindings.bind(Key::Char('\x1a'), EditAction::Undo); // ctrl-z
+            bindings.bind(Key::UnknownEscSeq(vec!['_']), EditAction::Redo); // alt-_
+            bindings.bind(Key::Char('\x0b'), EditAction::KillLine); // ctrl-k
+            bindings.bind(Key::Char('\x15'), EditAction::KillLineStart); // ctrl-u
+            bindings.bind(Key::Char('\x17'), EditAction::KillWordBackward); // ctrl-w
+            bindings.bind(Key::UnknownEscSeq(vec!['d']), EditAction::KillWordForward); // alt-d
+            bindings.bind(Key::Char('\x19'), EditAction::Yank); // ctrl-y
+            bindings.bind(Key::UnknownEscSeq(vec!['y']), EditAction::YankRotate); // alt-y
+        }
+
+        bindings
+    }
+
+    pub fn bind(&mut self, key: Key, action: EditAction) {
+        self.bindings.insert(key, action);
+    }
+
+    pub fn unbind(&mut self, key: &Key) {
+        self.bindings.remove(key);
+    }
+
+    pub(crate) fn extend(&mut self, other: &KeyBindings) {
+        for (key, action) in &other.bindings {
+            self.bindings.insert(key.clone(), *action);
+        }
+    }
+
+    pub fn get(&self, key: &Key) -> Option<EditAction> {
+        self.bindings.get(key).copied()
+    }
+}
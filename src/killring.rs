@@ -0,0 +1,140 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+// Most kills kept around for Alt-Y, like emacs' kill-ring-max but smaller
+const KILL_RING_SIZE: usize = 32;
+
+// Text removed with the kill commands, newest last. Consecutive kills are
+// collected into a single entry, so Ctrl-Y brings back all of it at once.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct KillRing {
+    entries: Vec<String>,
+    yank_idx: usize, // Entry inserted by the last yank, counted from the newest
+}
+
+impl KillRing {
+    pub(crate) fn new() -> Self {
+        KillRing::default()
+    }
+
+    // Store killed text, `append` continues the previous kill: text killed
+    // backwards goes in front of it, text killed forwards behind it
+    pub(crate) fn kill(&mut self, text: &str, append: bool, backward: bool) {
+        if text.is_empty() {
+            return;
+        }
+
+        match self.entries.last_mut() {
+            Some(last) if append => {
+                if backward {
+                    last.insert_str(0, text);
+                } else {
+                    last.push_str(text);
+                }
+            }
+            _ => {
+                self.entries.push(text.to_string());
+                if self.entries.len() > KILL_RING_SIZE {
+                    self.entries.remove(0);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn latest(&self) -> Option<&str> {
+        self.entries.last().map(String::as_str)
+    }
+
+    // Newest kill, the entry Ctrl-Y inserts
+    pub(crate) fn yank(&mut self) -> Option<&str> {
+        self.yank_idx = 0;
+        self.latest()
+    }
+
+    // Next older kill after a yank, wrapping around to the newest
+    pub(crate) fn rotate(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        self.yank_idx = (self.yank_idx + 1) % self.entries.len();
+        self.entries.get(self.entries.len() - 1 - self.yank_idx).map(String::as_str)
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+// OSC 52 sequence asking the terminal to put `text` on the system clipboard.
+// The terminal does the work, so it also works over SSH without any tools.
+pub(crate) fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kill_ring() {
+        let mut ring = KillRing::new();
+        ring.kill("world", false, false);
+        ring.kill("hello ", true, true);
+        ring.kill("ls -l", false, false);
+        ring.kill("", false, false);
+
+        assert_eq!(ring.yank(), Some("ls -l"));
+        assert_eq!(ring.rotate(), Some("hello world"));
+        assert_eq!(ring.rotate(), Some("ls -l"));
+        assert_eq!(KillRing::new().yank(), None);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("ls -l ~/ä".as_bytes()), "bHMgLWwgfi/DpA==");
+        assert_eq!(osc52("hi"), "\x1b]52;c;aGk=\x07");
+    }
+}
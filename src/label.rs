@@ -0,0 +1,194 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use console::Style;
+
+// Information about the line a dynamic label is computed for
+pub struct LabelContext {
+    line: usize,
+    last_status: Option<i32>,
+}
+
+impl LabelContext {
+    pub(crate) fn new(line: usize, last_status: Option<i32>) -> Self {
+        LabelContext { 
+            line, 
+            last_status 
+        }
+    }
+
+    // Number of the line being entered, starting at 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // Status reported by the callback for the previous command
+    pub fn last_status(&self) -> Option<i32> {
+        self.last_status
+    }
+
+    pub fn cwd(&self) -> Option<PathBuf> {
+        std::env::current_dir().ok()
+    }
+
+    // Name of the checked out git branch (or the short commit hash of a detached HEAD)
+    pub fn git_branch(&self) -> Option<String> {
+        let cwd = self.cwd()?;
+        let git_dir = find_git_dir(&cwd)?;
+        let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+
+        match head.strip_prefix("ref: ") {
+            Some(reference) => Some(reference.trim_start_matches("refs/heads/").to_string()),
+            None => Some(head.chars().take(7).collect())
+        }
+    }
+}
+
+// Walk up from `dir` until a .git directory (or worktree .git file) is found
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    for ancestor in dir.ancestors() {
+        let candidate = ancestor.join(".git");
+
+        if candidate.is_dir() {
+            return Some(candidate);
+        }
+
+        if candidate.is_file() {
+            let content = fs::read_to_string(&candidate).ok()?;
+            let git_dir = content.trim().strip_prefix("gitdir: ")?;
+            return Some(ancestor.join(git_dir));
+        }
+    }
+
+    None
+}
+
+type LabelFn = Arc<dyn Fn(&LabelContext) -> String + Send + Sync>;
+
+#[derive(Clone)]
+enum Segment {
+    Text(String, Option<Style>),
+    Dynamic(LabelFn, Option<Style>),
+}
+
+// Prompt label built from plain, styled and dynamic segments
+#[derive(Clone, Default)]
+pub struct Label {
+    segments: Vec<Segment>,
+}
+
+impl Label {
+    pub fn new() -> Self {
+        Label { 
+            segments: Vec::new() 
+        }
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.segments.push(Segment::Text(text.to_string(), None));
+        self
+    }
+
+    pub fn styled(mut self, text: &str, style: Style) -> Self {
+        self.segments.push(Segment::Text(text.to_string(), Some(style)));
+        self
+    }
+
+    // Segment computed each time a new line is prompted for
+    pub fn dynamic<F>(mut self, segment: F) -> Self
+    where F: Fn(&LabelContext) -> String + Send + Sync + 'static {
+        self.segments.push(Segment::Dynamic(Arc::new(segment), None));
+        self
+    }
+
+    pub fn dynamic_styled<F>(mut self, segment: F, style: Style) -> Self
+    where F: Fn(&LabelContext) -> String + Send + Sync + 'static {
+        self.segments.push(Segment::Dynamic(Arc::new(segment), Some(style)));
+        self
+    }
+
+    // Build the label string, styles are applied as ANSI escape codes
+    pub fn render(&self, ctx: &LabelContext) -> String {
+        let mut label = String::new();
+
+        for segment in &self.segments {
+            let (text, style) = match segment {
+                Segment::Text(text, style) => (text.clone(), style),
+                Segment::Dynamic(segment, style) => (segment(ctx), style)
+            };
+
+            match style {
+                Some(style) => label.push_str(&style.apply_to(text).to_string()),
+                None => label.push_str(&text)
+            }
+        }
+
+        label
+    }
+}
+
+impl From<&str> for Label {
+    fn from(text: &str) -> Self {
+        Label::new().text(text)
+    }
+}
+
+impl From<String> for Label {
+    fn from(text: String) -> Self {
+        Label::new().text(&text)
+    }
+}
+
+impl fmt::Debug for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Label")
+            .field("segments", &self.segments.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use console::measure_text_width;
+
+    #[test]
+    fn test_render_width_ignores_styles() {
+        let label = Label::new()
+            .styled("[", Style::new().red().force_styling(true))
+            .dynamic(|ctx| ctx.line().to_string())
+            .text("]>");
+
+        let rendered = label.render(&LabelContext::new(12, None));
+
+        assert!(rendered.contains("\x1b["));
+        assert_eq!(console::strip_ansi_codes(&rendered), "[12]>");
+        assert_eq!(measure_text_width(&rendered), 5);
+    }
+}
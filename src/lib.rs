@@ -24,269 +24,632 @@
 
 extern crate console;
 
+use std::io;
+use std::time::Instant;
 use console::Term;
-use std::io::{self, stdout, Write};
-
-mod data;
-use data::InputData;
 
 mod hooks;
 use hooks::Hooks;
 
 mod prompt;
-use prompt::prompt;
+use prompt::{prompt, prompt_unattended, PromptContext};
+pub use prompt::ReadResult;
+
+mod render;
+use render::{PromptLabels, Renderer};
+
+mod masked;
+pub use masked::read_masked;
+pub use zeroize::Zeroizing;
 
 mod log;
-use log::LogStream;
-
-pub struct CliHistorySettings<'a> {
-    label: &'a str,
-    max_size: usize,
-    max_size_log_file: usize,
-    die_on_exit: bool,
-    log_file_path: &'a str,
+mod expand;
+use expand::{expand_history, ExpansionError};
+mod format;
+pub use format::LogFormat;
+
+mod history;
+pub use history::HistoryEntry;
+
+mod store;
+pub use store::{FileStore, HistoryStore, MemoryStore};
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteHistory;
+
+mod redact;
+pub use redact::{Redactor, RegexRedactor, REDACTED};
+
+mod tokenize;
+pub use tokenize::{tokenize, TokenizeError};
+
+mod alias;
+mod builtins;
+use builtins::{Builtin, ALIAS_BUILTINS, HISTORY_BUILTINS};
+
+mod command;
+pub use command::{Command, CommandRegistry};
+
+mod label;
+pub use label::{Label, LabelContext};
+
+mod status;
+pub use status::CommandStatus;
+
+mod killring;
+use killring::KillRing;
+
+mod keymap;
+pub use keymap::{parse_key, EditAction, EditMode, KeyBindings};
+
+mod settings;
+pub use settings::{CliHistorySettings, CliHistorySettingsBuilder};
+
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "config")]
+pub use config::{ConfigError, CONFIG_ENV};
+
+pub struct CliHistory {
+    history: Vec<HistoryEntry>, // Data pool
+    line: usize, // Number of the line currently prompted for
+    last_status: Option<i32>, // Status reported by the callback for the last command
+    session_id: String, // Stored along with every history entry
+    store: Option<Box<dyn HistoryStore + Send>>, // Persistent history, `history` caches its newest entries
+    logged: usize, // Entries written to the store by this session
+    unreported: Option<Instant>, // Line returned by read_line() waiting for its status, read at this time
+    completions: Vec<String>, // Command names completed with Tab
+    kill_ring: KillRing, // Shared by all lines edited with this history
+    deleted: Vec<HistoryEntry>, // Removed by `history -d`, taken out of the store by `history -w`
+    cleared: bool, // `history -c` ran, `history -w` clears the store
+    settings: CliHistorySettings
 }
 
-impl<'a> CliHistorySettings<'a> {
-    pub fn new() -> Self {
-        CliHistorySettings { 
-            label: "CliHistoryPrompt: ",
-            max_size: 500,
-            max_size_log_file: 500,
-            die_on_exit: false,
-            log_file_path: "",
+impl CliHistory {
+    pub fn new<S: Into<CliHistorySettings>>(settings: S) -> Self {
+        CliHistory {
+            history: Vec::new(), // Command pool
+            line: 1,
+            last_status: None,
+            session_id: history::new_session_id(),
+            store: None,
+            logged: 0,
+            unreported: None,
+            completions: Vec::new(),
+            kill_ring: KillRing::new(),
+            deleted: Vec::new(),
+            cleared: false,
+            settings: settings.into()
         }
     }
 
-    pub fn set_label(&mut self, label: &'a str) {
-        self.label = label;
+    pub fn settings(&self) -> &CliHistorySettings {
+        &self.settings
     }
 
-    pub fn set_max_size(&mut self, max_size: usize) {
-        self.max_size = max_size;
+    // Settings can be changed while the history is alive, they apply from the next prompt on
+    pub fn settings_mut(&mut self) -> &mut CliHistorySettings {
+        &mut self.settings
     }
 
-    pub fn set_die_on_exit(&mut self) {
-        self.die_on_exit = true;
+    pub fn set_settings(&mut self, settings: CliHistorySettings) {
+        self.settings = settings;
     }
 
-    pub fn set_max_size_log_file(&mut self, max_size: usize) {
-        self.max_size_log_file = max_size
-    } 
+    fn get_labels(&self) -> PromptLabels {
+        // Dynamic label segments are computed once for every new line
+        let ctx = LabelContext::new(self.line, self.last_status);
 
-    pub fn set_log_to_file(&mut self, file_path: &'a str) {
-        self.log_file_path = file_path;
-    } 
-}
+        PromptLabels {
+            left: self.settings.label.render(&ctx),
+            right: self.settings.right_label.render(&ctx)
+        }
+    }
 
-pub struct CliHistory<'a> {
-    history: Vec<String>, // Data pool
-    idx: usize, // History pool data index
-    settings: &'a CliHistorySettings<'a>
-}
+    // Ask the user for input, starting with the history entry `recall` if given
+    fn launch_prompt(&mut self, term: &Term, renderer: &mut Renderer, labels: &PromptLabels, initial: &str, recall: Option<usize>) -> String {
+        let commands = self.completion_names(); // Aliases may have changed since the last line
+        let start = recall.unwrap_or(self.history.len());
+        let history = self.history.clone();
+        let mut sync = || CliHistory::sync_for_editor(&mut self.store, &mut self.history, &self.settings);
+        let ctx = PromptContext { 
+            history, 
+            start,
+            commands: &commands, 
+            kill_ring: &mut self.kill_ring,
+            sync: &mut sync
+        };
 
-impl<'a> CliHistory<'a> {
-    pub fn new(settings: &'a CliHistorySettings<'a>) -> Self {
-        CliHistory {
-            history: Vec::new(), // Command pool
-            idx: 0, // Need to navigate through the input history
-            settings: settings
+        match prompt(term, renderer, &self.settings, labels, initial, ctx) {
+            Ok(ReadResult::Line(line)) => line,
+            _ => String::new()
         }
     }
 
-    fn set_label(&self, ignore: bool) {
-        // Show the user that we want some input!! 
-        if ignore {
-            print!("{} ", self.settings.label);
-        } else {
-            print!("\r{} ", self.settings.label);
+    // Read a single line with editing and history navigation, leaving the loop to the caller.
+    // A non-empty line is recorded in the history before it is returned.
+    pub fn read_line(&mut self) -> io::Result<ReadResult> {
+        let term = Term::stdout();
+        self.open_configured_store(&term);
+        self.store_unreported(&term);
+
+        let labels = self.get_labels();
+        let mut initial = String::new();
+
+        if term.is_term()
+            && let Err(err) = self.sync_shared_history() {
+            term.write_line(&format!("Error reading the shared history: {}", err))?;
         }
 
-        io::stdout().flush().unwrap();
+        let line = loop {
+            let result = if term.is_term() {
+                let mut renderer = Renderer::new(&term);
+                Hooks::enable_bracketed_paste(&term);
+                let commands = self.completion_names();
+                let history = self.history.clone();
+                let mut sync = || CliHistory::sync_for_editor(&mut self.store, &mut self.history, &self.settings);
+                let ctx = PromptContext { 
+                    start: history.len(),
+                    history, 
+                    commands: &commands, 
+                    kill_ring: &mut self.kill_ring,
+                    sync: &mut sync
+                };
+                let result = prompt(&term, &mut renderer, &self.settings, &labels, &initial, ctx);
+                Hooks::disable_bracketed_paste(&term);
+                result?
+            } else {
+                match prompt_unattended() {
+                    Some(line) => ReadResult::Line(line),
+                    None => ReadResult::Eof
+                }
+            };
+
+            let line = match result {
+                ReadResult::Line(line) => line,
+                ReadResult::Eof => return Ok(ReadResult::Eof)
+            };
+
+            match self.expand_line(&line) {
+                Ok(None) => break line,
+                Ok(Some(expanded)) if self.settings.confirm_expansion && term.is_term() => initial = expanded,
+                Ok(Some(expanded)) => {
+                    term.write_line(&expanded)?;
+                    break expanded;
+                }
+                Err(err) => {
+                    // Like bash, a line which can't be expanded is dropped
+                    term.write_line(&err.to_string())?;
+                    initial.clear();
+                }
+            }
+        };
+
+        self.line += 1;
+
+        if !line.is_empty() && self.value_add_history(&line) {
+            // Stored once the status is reported or the next line is read
+            self.unreported = Some(Instant::now());
+        }
+
+        Ok(ReadResult::Line(line))
     }
 
-    fn get_label(&self) -> String {
-        self.settings.label.to_string()
+    // Replace aliases (and variables if enabled) like the navigator does before calling
+    // the callback, e.g. for lines returned by read_line()
+    pub fn expand_command(&self, line: &str) -> String {
+        self.settings.expand_command(line)
     }
 
-    fn launch_prompt(&self, ignore: bool, no_lable: bool, last_char: char) -> String {
-        if !no_lable {
-            // Ignore the prompt label
-            self.set_label(ignore);
+    // History expansion of a submitted line, None if there is nothing to expand
+    fn expand_line(&self, line: &str) -> Result<Option<String>, ExpansionError> {
+        if !self.settings.history_expansion {
+            return Ok(None);
         }
 
-        // Ask the user for input..
-        if let Some(input) = prompt(self.settings.label.to_string(), last_char) {
-            input
-        } else {
-            String::new()
+        expand_history(line, &self.history)
+    }
+
+    // Status of the command read by read_line(), shown by dynamic labels and
+    // stored in its history entry together with the time it took to run
+    pub fn report_status<S: CommandStatus>(&mut self, status: S) {
+        self.last_status = status.status();
+
+        if let Some(started) = self.unreported
+            && let Some(entry) = self.history.last_mut() {
+            entry.duration = Some(started.elapsed());
+            entry.exit_status = self.last_status;
         }
+
+        self.store_unreported(&Term::stdout());
     }
 
-    fn value_add_history(&mut self, value: &str) {
-        if self.idx == self.settings.max_size {
+    fn store_unreported(&mut self, term: &Term) {
+        if self.unreported.take().is_some() {
+            self.record_last_entry(term);
+        }
+    }
+
+    // Returns false if the duplicate and ignore settings keep `value` out of the history
+    fn value_add_history(&mut self, value: &str) -> bool {
+        if self.settings.is_ignored(value) {
+            return false;
+        }
+
+        // Secrets are removed before the command enters the history, unless it may stay raw in memory
+        let command = if self.settings.keep_raw_in_memory {
+            value.to_string()
+        } else {
+            self.settings.redact(value)
+        };
+
+        if self.settings.ignore_dups && self.history.last().is_some_and(|entry| entry.command == command) {
+            return false;
+        }
+
+        if self.settings.erase_dups {
+            self.history.retain(|entry| entry.command != command);
+        }
+
+        if self.store.is_some() && !self.history.is_empty() && self.history.len() >= self.settings.max_size {
+            // The store keeps everything, only drop the oldest cached entry
+            self.history.remove(0);
+        }
+
+        if self.history.len() >= self.settings.max_size {
             self.history = Vec::new()
         }
 
-        self.history.push(value.to_string()); // Add element to history
-        self.idx = self.history.len(); // Update the index
+        self.history.push(HistoryEntry::new(&command, &self.session_id)); // Add element to history
+        true
+    }
+
+    // Write the newest entry to the history store
+    fn store_last_entry(&mut self) -> io::Result<()> {
+        let (store, entry) = match (self.store.as_mut(), self.history.last()) {
+            (Some(store), Some(entry)) => (store, self.settings.stored_entry(entry)),
+            _ => return Ok(())
+        };
+
+        if self.settings.erase_dups {
+            store.remove_command(&entry.command)?;
+        }
+
+        store.append(&entry)
     }
 
-    pub fn get_history(&mut self) -> &mut Vec<String> {
+    // Command names the first word of a line is completed from with Tab
+    pub fn set_completions(&mut self, names: Vec<String>) {
+        self.completions = names;
+    }
+
+    pub fn get_history(&mut self) -> &mut Vec<HistoryEntry> {
         &mut self.history 
     }
 
-    fn history_iter_up(&mut self) -> Option<&String> {
-        if self.idx > 0 {
-            self.idx -= 1; // Update index by arrow key up
-            return self.history.get(self.idx) // Fetch data by the new index
+    // Persist the history in `store` instead of the stores configured by the settings.
+    // The in-memory history is refilled with the newest entries of the store.
+    pub fn set_store<S: HistoryStore + Send + 'static>(&mut self, store: S) -> io::Result<usize> {
+        self.store = Some(Box::new(store));
+        self.load_history()
+    }
+
+    pub fn store(&self) -> Option<&(dyn HistoryStore + Send)> {
+        self.store.as_deref()
+    }
+
+    pub fn store_mut(&mut self) -> Option<&mut (dyn HistoryStore + Send + 'static)> {
+        self.store.as_deref_mut()
+    }
+
+    // Open the store configured by the settings unless one is attached already
+    fn open_configured_store(&mut self, term: &Term) {
+        if self.store.is_some() {
+            return;
+        }
+
+        match self.open_store() {
+            Ok(store) => {
+                self.store = store;
+
+                if let Err(err) = self.load_history() {
+                    term.write_line(&format!("Error loading the history: {}", err)).unwrap();
+                }
+            }
+            Err(err) => term.write_line(&format!("Error opening the history: {}", err)).unwrap()
+        }
+    }
+
+    // Write the newest entry to the store while the session is within max_size_log_file
+    fn record_last_entry(&mut self, term: &Term) {
+        if self.logged > self.settings.max_size_log_file {
+            return;
         }
 
-        None
+        if let Err(err) = self.store_last_entry() {
+            term.write_line(&format!("Failed to append command to file: {}", err)).unwrap();
+        }
+
+        self.logged += 1;
     }
 
-    fn history_iter_down(&mut self) -> Option<&String> {
-        if self.idx < self.history.len() {
-            self.idx += 1; // Update index by arrow key down
-            return self.history.get(self.idx.saturating_sub(1)) 
+    // Store configured by the settings: SQLite database or history file
+    fn open_store(&self) -> io::Result<Option<Box<dyn HistoryStore + Send>>> {
+        #[cfg(feature = "sqlite")]
+        if !self.settings.sqlite_path.is_empty() {
+            let store = SqliteHistory::open(&self.settings.sqlite_path).map_err(io::Error::other)?;
+            return Ok(Some(Box::new(store)));
         }
 
-        None
+        if !self.settings.log_file_path.is_empty() {
+            let store = FileStore::structured(&self.settings.log_file_path, self.settings.log_format)?;
+            return Ok(Some(Box::new(store)));
+        }
+
+        Ok(None)
     }
 
-    fn check_hook_enter(term: &Term, hooks: &mut Hooks) -> bool {
-        if let Ok(key) = term.read_key() {
-            hooks.update(key);
-            hooks.is_enter()
+    // Fill the in-memory history with the newest max_size entries of the store,
+    // this includes commands of earlier sessions. Returns the number of loaded entries.
+    pub fn load_history(&mut self) -> io::Result<usize> {
+        if let Some(ref store) = self.store {
+            self.history = store.recent(self.settings.max_size)?;
+            self.deleted.clear();
+            self.cleared = false;
+        }
+
+        Ok(self.history.len())
+    }
+
+    // Pick up the commands other sessions wrote to the shared store since the last time.
+    // Returns the number of entries which were new to the in-memory history.
+    fn sync_shared_history(&mut self) -> io::Result<usize> {
+        CliHistory::sync_store(&mut self.store, &mut self.history, &self.settings)
+    }
+
+    // sync_shared_history() on the fields only, so it can run while the editor holds others
+    fn sync_store(store: &mut Option<Box<dyn HistoryStore + Send>>, history: &mut Vec<HistoryEntry>, 
+        settings: &CliHistorySettings) -> io::Result<usize> {
+        let store = match store {
+            Some(store) if settings.share_history => store,
+            _ => return Ok(0)
+        };
+
+        store.sync()?;
+        let shared = store.recent(settings.max_size)?;
+        Ok(history::merge_shared(history, shared, settings.max_size))
+    }
+
+    // Sync run by the editor when HistoryUp leaves the line being entered, returns
+    // the updated history if other sessions added something. Errors are left to the
+    // next sync outside the editor, a message would break the prompt drawn.
+    fn sync_for_editor(store: &mut Option<Box<dyn HistoryStore + Send>>, history: &mut Vec<HistoryEntry>, 
+        settings: &CliHistorySettings) -> Option<Vec<HistoryEntry>> {
+        match CliHistory::sync_store(store, history, settings) {
+            Ok(added) if added > 0 => Some(history.clone()),
+            _ => None
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    fn run_callback<CommandCallback, Status>(&mut self, callback: &CommandCallback, input: &str)
+    where CommandCallback: Fn(&str) -> Status, Status: CommandStatus {
+        self.last_status = callback(input).status();
+        self.line += 1;
+    }
+
+    // Run the callback for a freshly added entry and record how it went
+    fn run_recorded_callback<CommandCallback, Status>(&mut self, callback: &CommandCallback, input: &str)
+    where CommandCallback: Fn(&str) -> Status, Status: CommandStatus {
+        let started = Instant::now();
+        self.run_callback(callback, input);
+
+        if let Some(entry) = self.history.last_mut() {
+            entry.duration = Some(started.elapsed());
+            entry.exit_status = self.last_status;
+        }
+    }
+
+    // Hand a submitted line to the callback and record it in the history. The history
+    // keeps the line as entered, the callback gets aliases and variables replaced.
+    fn run_line<CommandCallback, Status>(&mut self, term: &Term, input: &str, callback: &CommandCallback)
+    where CommandCallback: Fn(&str, &str) -> Status, Status: CommandStatus {
+        let command = self.expand_command(input);
+        let callback = |command: &str| callback(command, input);
+
+        if !self.value_add_history(input) {
+            self.run_callback(&callback, &command); // Not recorded anywhere
         } else {
-            false
+            self.run_recorded_callback(&callback, &command);
+
+            // Written after the callback so duration and exit status are known
+            self.record_last_entry(term);
+        }
+    }
+
+    // Run the navigator dispatching every line to the commands of `registry`.
+    // Command names are completed with Tab, exit words stop the navigator as usual.
+    pub fn launch_commands(&mut self, registry: &CommandRegistry) -> String {
+        // Added to the completions the application set
+        self.completions.extend(registry.names());
+        let settings = self.settings.clone();
+
+        self.navigate(|command: &str, line: &str| {
+            // Exit words are checked as typed, an alias can't turn a line into one
+            if settings.is_exit_word(line) {
+                return None;
+            }
+
+            registry.dispatch(command)
+        })
+    }
+
+    // Names completed with Tab: the completions set by the application, the enabled
+    // built-in commands and the aliases defined right now
+    fn completion_names(&self) -> Vec<String> {
+        let mut names = self.completions.clone();
+
+        if self.settings.history_builtins {
+            names.extend(HISTORY_BUILTINS.iter().map(|name| name.to_string()));
         }
+        if self.settings.alias_builtins {
+            names.extend(ALIAS_BUILTINS.iter().map(|name| name.to_string()));
+        }
+        names.extend(self.settings.aliases().keys().cloned());
+        names.sort();
+        names.dedup();
+
+        names
+    }
+
+    // Like launch_navigator(), the callback receives the line split into arguments by tokenize()
+    // along with the raw line. Lines which can't be split are reported instead, with status 2.
+    pub fn launch_navigator_with_args<CommandCallback, Status>(&mut self, callback: CommandCallback) -> String
+    where CommandCallback: Fn(&[String], &str) -> Status, Status: CommandStatus {
+        self.launch_navigator(|line: &str| match tokenize(line) {
+            Ok(args) => callback(&args, line).status(),
+            Err(err) => {
+                let _ = Term::stderr().write_line(&err.to_string());
+                Some(2)
+            }
+        })
     }
 
-    fn print_prompt_history(&self, term: &mut Term, input: &String, data_len: usize) {
-        term.write(format!("\r{} {}{:>len$}", self.get_label(), input, " ", len=data_len).as_bytes()).unwrap();
-        term.flush().unwrap();
+    pub fn launch_navigator<CommandCallback, Status>(&mut self, callback: CommandCallback) -> String 
+    where CommandCallback: Fn(&str) -> Status, Status: CommandStatus {
+        self.navigate(|command: &str, _line: &str| callback(command))
     }
 
-    pub fn launch_navigator<CommandCallback>(&mut self, callback: CommandCallback) -> String 
-    where CommandCallback: Fn(&str) {
-        let mut term = Term::stdout();
+    // The navigator loop, `callback` receives the expanded command and the line as typed
+    fn navigate<CommandCallback, Status>(&mut self, callback: CommandCallback) -> String 
+    where CommandCallback: Fn(&str, &str) -> Status, Status: CommandStatus {
+        let term = Term::stdout();
+        let mut renderer = Renderer::new(&term);
         let mut hooks = Hooks::new();
+        let bindings = self.settings.key_bindings();
         let mut input = String::new(); // Return the value selected by the user.
-        let mut switch = false;
-        let mut last_char: Option<char> = None;
-        let mut file_stream = LogStream::new(self.settings.log_file_path.to_string());
-        let mut log_count = 0;
+        let mut pending: Option<String> = None; // Input typed before the editor took over
+        let mut recall: Option<usize> = None; // History entry the editor starts with
 
-        if !self.settings.log_file_path.is_empty() {
-            if let Err(err) = file_stream.create_log_file() {
-                term.write_line(&format!("Error creating {}: {}", self.settings.log_file_path, err)).unwrap();
-            }
-        }
+        self.open_configured_store(&term);
+        self.store_unreported(&term);
+
+        Hooks::enable_bracketed_paste(&term);
 
         'outer: loop {
+            let labels = self.get_labels();
             input.clear();
 
-            if let Some(c) = last_char {
-                if switch {
-                    // Ignore the prompt label to avoid visual feedback issues..
-                    input = self.launch_prompt(true, true, c);
-                } else {
-                    // Display full prompt
-                    input = self.launch_prompt(true, false, c);
+            if !term.is_term() {
+                // Nobody is attending the terminal, process plain lines from stdin
+                match prompt_unattended() {
+                    Some(line) if !line.is_empty() => input = line,
+                    _ => break 'outer
                 }
+            } else if let Some(initial) = pending.take() {
+                input = self.launch_prompt(&term, &mut renderer, &labels, &initial, recall.take());
             }
 
-            term.flush().unwrap();
-
             if !input.is_empty() {
-                self.value_add_history(&input);
-                
-                if log_count <= self.settings.max_size_log_file {
-                    if let Err(err) = file_stream.append_log_file(input.as_str()) {
-                        term.write_line(&format!("Failed to append command to file: {}", err)).unwrap();
+                match self.expand_line(&input) {
+                    Ok(None) => {},
+                    Ok(Some(expanded)) if self.settings.confirm_expansion && term.is_term() => {
+                        // Let the user look at the expanded line before it runs
+                        pending = Some(expanded);
+                        continue 'outer;
+                    }
+                    Ok(Some(expanded)) => {
+                        term.write_line(&expanded).unwrap();
+                        input = expanded;
+                    }
+                    Err(err) => {
+                        term.write_line(&err.to_string()).unwrap();
+                        input.clear();
+                    }
+                }
+            }
+
+            if !input.is_empty() && (self.settings.history_builtins || self.settings.alias_builtins) {
+                match self.run_builtin(&term, &input) {
+                    Builtin::None => {},
+                    Builtin::Done => input.clear(),
+                    Builtin::Edit(command) if term.is_term() => {
+                        pending = Some(command);
+                        continue 'outer;
                     }
-                    
-                    log_count += 1;
+                    Builtin::Edit(command) => input = command // Nobody could edit it, run it as it is
                 }
+            }
 
-                callback(&input);
+            if !input.is_empty() {
+                self.run_line(&term, &input, &callback);
             }
 
-            if self.settings.die_on_exit && input == "exit".to_string() {
+            if self.settings.is_exit_word(&input) {
                 // Initialized with die_on_exit set to true
-                stdout().flush().unwrap();
+                term.flush().unwrap();
                 break 'outer;
             }
 
+            if !term.is_term() {
+                continue 'outer;
+            }
+
             'inner: loop {
-                term.write(format!("\r{} ", self.get_label()).as_bytes()).unwrap();
+                renderer.draw(&term, &labels, &[], 0).unwrap();
                 
                 if let Ok(key) = term.read_key() {
-                    hooks.update(key.clone()); // Update the key state!
+                    hooks.update(&key, &bindings); // Update the key state!
 
                     if hooks.is_arrow_up() {
-                        // Arrow up key was pressed: navigate from history last index to first
-                        if let Some(command) = self.history_iter_up() {
-                            let input_data = InputData::new(command.to_string(), input.chars().count());
-                            input = input_data.data.clone();
-
-                            if !input_data.data.is_empty() {
-                                // Write input data to stdout
-                                self.print_prompt_history(&mut term, &input, input_data.len);
-
-                                if CliHistory::check_hook_enter(&term, &mut hooks) {
-                                    callback(&input); // Send input to caller
-                                    break 'outer;
-                                }
-                            }
+                        if let Err(err) = self.sync_shared_history() {
+                            term.write_line(&format!("Error reading the shared history: {}", err)).unwrap();
                         }
-                    } else if hooks.is_arrow_down() {
-                        // Arrow down key was pressed: navigate from history first index to last
-                        if let Some(command) = self.history_iter_down() {
-                            let input_data = InputData::new(command.to_string(), input.chars().count());
-                            input = input_data.data.clone();
-
-                            if !input_data.data.is_empty() {
-                                // Write input data to stdout
-                                self.print_prompt_history(&mut term, &input, input_data.len);
-
-                                if CliHistory::check_hook_enter(&term, &mut hooks) {
-                                    callback(&input); // Send input to caller
-                                    break 'outer;
-                                }
-                            }
+
+                        // Arrow up key was pressed: edit the newest entry, the editor browses the rest
+                        if !self.history.is_empty() {
+                            recall = Some(self.history.len() - 1);
+                            pending = Some(String::new());
+                            break 'inner;
                         }
                     } else if hooks.is_enter() {
+                        renderer.finish(&term).unwrap();
                         break 'outer;
+                    } else if Hooks::is_paste_start(&term, &key) {
+                        // Hand the pasted text over to the editor as a whole
+                        pending = Some(Hooks::read_paste(&term, self.settings.max_paste_size, self.settings.keep_paste_newlines));
+                        break 'inner;
                     } else {
                         // read_key() always ate the first char of the next command
                         // so we need to determine what was typed to "restore" the input
                         // eaten by read_key
                         if let Some(pressed_char) = Hooks::get_char(key) {
-                            term.write(pressed_char.to_string().as_bytes()).unwrap();
-
-                            last_char = Some(pressed_char);
-                            switch = true
+                            pending = Some(pressed_char.to_string());
+                            break 'inner;
                         }
-
-                        break 'inner;
-                    }
-
-                    if self.settings.die_on_exit && input == "exit".to_string() {
-                        stdout().flush().unwrap();
-                        break 'outer;
                     }
-
-                    term.flush().unwrap();
                 }
             }
         }
 
+        Hooks::disable_bracketed_paste(&term);
+
         input
     }
 }
 
+impl Drop for CliHistory {
+    fn drop(&mut self) {
+        // Don't lose the last line read when no status was reported for it
+        self.store_unreported(&Term::stdout());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -306,4 +669,42 @@ mod tests {
         let input = cli_history.launch_navigator(callback);
         dbg!(input);
     }
+
+    #[test]
+    fn test_store_without_cache() {
+        let mut settings = CliHistorySettings::new();
+        settings.set_max_size(0);
+
+        let mut cli_history = CliHistory::new(&settings);
+        cli_history.set_store(MemoryStore::new()).unwrap();
+
+        for command in ["ls", "pwd"] {
+            assert!(cli_history.value_add_history(command));
+            cli_history.store_last_entry().unwrap();
+        }
+
+        assert_eq!(cli_history.store().unwrap().len().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_redacted_history() {
+        let mut settings = CliHistorySettings::new();
+        settings.add_redaction_rule(r"token=\S+", "token=[REDACTED]").unwrap();
+
+        let mut cli_history = CliHistory::new(&settings);
+        cli_history.set_store(MemoryStore::new()).unwrap();
+
+        let seen = std::cell::RefCell::new(Vec::new());
+        let callback = |command: &str, line: &str| seen.borrow_mut().push((command.to_string(), line.to_string()));
+
+        cli_history.run_line(&Term::stdout(), "curl token=abc", &callback);
+        assert_eq!(seen.borrow()[0], ("curl token=abc".to_string(), "curl token=abc".to_string()));
+        assert_eq!(cli_history.get_history()[0].command, "curl token=[REDACTED]");
+
+        // Raw in memory, the store still gets the redacted line
+        cli_history.settings.set_keep_raw_in_memory();
+        cli_history.run_line(&Term::stdout(), "wget token=xyz", &callback);
+        assert_eq!(cli_history.get_history()[1].command, "wget token=xyz");
+        assert_eq!(cli_history.store().unwrap().get(1).unwrap().unwrap().command, "wget token=[REDACTED]");
+    }
 }
@@ -25,17 +25,36 @@
 use std::io;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use super::format::{self, LogFormat};
+use super::history::HistoryEntry;
+
+// Run `op` while holding the advisory lock of `file`. Sessions sharing a history file
+// take the exclusive lock for writing and the shared lock for reading.
+fn with_lock<T>(file: &File, exclusive: bool, op: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    if exclusive {
+        file.lock()?;
+    } else {
+        file.lock_shared()?;
+    }
+
+    let result = op();
+    file.unlock()?;
+    result
+}
 
 pub(super) struct LogStream {
     path: String,
+    format: LogFormat,
     stream: Option<File>,
 }
 
 impl LogStream {
-    pub(super) fn new(path: String) -> Self {
+    pub(super) fn new(path: String, format: LogFormat) -> Self {
         LogStream { 
             path,
+            format,
             stream: None, 
         }
     }
@@ -44,7 +63,7 @@ impl LogStream {
         // Create history log file if it doesn't already exist 
         // and share the open stream within this implementation
         self.stream = Some(OpenOptions::new()
-            .write(true)
+            .read(true)
             .append(true)
             .create(true) 
             .open(&self.path)?);
@@ -52,13 +71,79 @@ impl LogStream {
         Ok(())
     }
     
-    pub(super) fn append_log_file(&mut self, content: &str) -> Result<(), io::Error> {
-        if let Some(ref mut stream) = self.stream {
-            // Ensure we can write to the file stream and append the content if able
-            stream.write_all(format!("{}\n", content).as_bytes())?;
-            stream.flush()?;
+    // Append `entry`, returns the offsets of the file where it starts and ends
+    pub(super) fn append_log_file(&mut self, entry: &HistoryEntry) -> Result<Option<(u64, u64)>, io::Error> {
+        let stream = match self.stream {
+            Some(ref stream) => stream,
+            None => return Ok(None)
+        };
+
+        // The whole entry goes out with a single append while locked,
+        // other sessions never see a partially written entry
+        let content = format::encode(self.format, entry);
+        with_lock(stream, true, || {
+            let start = stream.metadata()?.len();
+            let mut writer = stream;
+            writer.write_all(content.as_bytes())?;
+            writer.flush()?;
+
+            Ok(Some((start, start + content.len() as u64)))
+        })
+    }
+
+    pub(super) fn truncate_log_file(&mut self) -> Result<(), io::Error> {
+        if let Some(ref stream) = self.stream {
+            with_lock(stream, true, || stream.set_len(0))?;
         }
 
         Ok(())
     }
+
+    // Rewrite the file with the entries `keep` returns true for, returns the kept entries
+    // and the new length of the file. Read and rewrite happen under one lock so entries
+    // appended by others aren't lost.
+    pub(super) fn retain_log_file<F>(&mut self, keep: F) -> Result<(Vec<HistoryEntry>, u64), io::Error>
+    where F: FnMut(&HistoryEntry) -> bool {
+        let stream = match self.stream {
+            Some(ref stream) => stream,
+            None => return Ok((Vec::new(), 0))
+        };
+
+        with_lock(stream, true, || {
+            let mut file = stream;
+            let mut content = String::new();
+            file.seek(SeekFrom::Start(0))?;
+            file.read_to_string(&mut content)?;
+
+            let mut entries = format::decode(self.format, &content);
+            entries.retain(keep);
+
+            let content: String = entries.iter().map(|entry| format::encode(self.format, entry)).collect();
+            stream.set_len(0)?;
+            file.write_all(content.as_bytes())?;
+            file.flush()?;
+
+            Ok((entries, content.len() as u64))
+        })
+    }
+
+    // Entries written from `offset` on, together with the offset the file ends at.
+    // A file shorter than `offset` was truncated, nothing is read from it then.
+    pub(super) fn read_log_file(&self, offset: u64) -> Result<(Vec<HistoryEntry>, u64), io::Error> {
+        let file = File::open(&self.path)?;
+        let mut content = String::new();
+
+        let end = with_lock(&file, false, || {
+            let len = file.metadata()?.len();
+            if len < offset {
+                return Ok(len);
+            }
+
+            let mut reader = &file;
+            reader.seek(SeekFrom::Start(offset))?;
+            Ok(offset + reader.read_to_string(&mut content)? as u64)
+        })?;
+
+        Ok((format::decode(self.format, &content), end))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::io::{self, Read};
+use console::{Key, Term};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::render::{PromptLabels, Renderer};
+
+// Initial capacity of the secret, grown by hand so no copy is left behind on reallocation
+const SECRET_CAPACITY: usize = 128;
+
+// Append `c` to the secret without letting String reallocate on its own
+fn push_secret(secret: &mut Zeroizing<String>, c: char) {
+    if secret.len() + c.len_utf8() > secret.capacity() {
+        let mut grown = Zeroizing::new(String::with_capacity((secret.capacity() * 2).max(SECRET_CAPACITY)));
+        grown.push_str(secret);
+        *secret = grown; // The old buffer is zeroized when dropped
+    }
+
+    secret.push(c);
+}
+
+// Read a line from `reader` char by char into `secret`, the line break is dropped.
+// Invalid UTF-8 is replaced by U+FFFD.
+fn read_secret_line<R: Read>(reader: &mut R, secret: &mut Zeroizing<String>) -> io::Result<()> {
+    let mut buf = Zeroizing::new([0u8; 4]);
+
+    loop {
+        if reader.read(&mut buf[..1])? == 0 {
+            break;
+        }
+
+        // Bytes which can't start a char are taken on their own
+        let len = match buf[0] {
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => 1
+        };
+        if len > 1 {
+            reader.read_exact(&mut buf[1..len])?;
+        }
+
+        match std::str::from_utf8(&buf[..len]).ok().and_then(|s| s.chars().next()) {
+            Some('\n') => break,
+            Some('\r') => {}
+            Some(c) => push_secret(secret, c),
+            None => push_secret(secret, char::REPLACEMENT_CHARACTER)
+        }
+    }
+
+    Ok(())
+}
+
+// Read a line without recording it anywhere, e.g. a password. Nothing is echoed
+// with `mask` set to None, otherwise every char is displayed as `mask`.
+// The returned buffer is zeroized when dropped.
+pub fn read_masked(label: &str, mask: Option<char>) -> io::Result<Zeroizing<String>> {
+    let term = Term::stdout();
+    let mut secret = Zeroizing::new(String::with_capacity(SECRET_CAPACITY));
+
+    if !term.is_term() {
+        // Nobody is attending the terminal, take the line from stdin
+        read_secret_line(&mut io::stdin().lock(), &mut secret)?;
+        return Ok(secret);
+    }
+
+    let mut renderer = Renderer::new(&term);
+    let labels = PromptLabels {
+        left: label.to_string(),
+        right: String::new()
+    };
+
+    loop {
+        // Only the mask chars ever reach the renderer
+        let shown: Vec<char> = match mask {
+            Some(mask) => vec![mask; secret.chars().count()],
+            None => Vec::new()
+        };
+        renderer.draw(&term, &labels, &shown, shown.len())?;
+
+        match term.read_key()? {
+            Key::Enter => break,
+            Key::Backspace => {
+                secret.pop();
+            }
+            Key::Char('\x15') => secret.zeroize(), // Ctrl-U discards everything typed so far
+            Key::Char(c) if !c.is_control() => push_secret(&mut secret, c),
+            _ => {}
+        }
+    }
+
+    renderer.finish(&term)?;
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_secret_grows() {
+        let mut secret = Zeroizing::new(String::with_capacity(2));
+        for c in "päss".chars() {
+            push_secret(&mut secret, c);
+        }
+
+        assert_eq!(secret.as_str(), "päss");
+        assert!(secret.capacity() >= 5);
+    }
+
+    #[test]
+    fn test_read_secret_line() {
+        let mut input = io::Cursor::new("pä$$wörd\r\nnext line\n".as_bytes());
+        let mut secret = Zeroizing::new(String::with_capacity(2));
+        read_secret_line(&mut input, &mut secret).unwrap();
+        assert_eq!(secret.as_str(), "pä$$wörd");
+
+        let mut secret = Zeroizing::new(String::new());
+        read_secret_line(&mut io::Cursor::new(b"no newline"), &mut secret).unwrap();
+        assert_eq!(secret.as_str(), "no newline");
+
+        let mut secret = Zeroizing::new(String::new());
+        read_secret_line(&mut io::Cursor::new(b"\xffab\n"), &mut secret).unwrap();
+        assert_eq!(secret.as_str(), "\u{fffd}ab");
+    }
+}
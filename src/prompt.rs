@@ -22,11 +22,26 @@
 * SOFTWARE.
 */
 
-use std::io::Write;
+use std::io;
 use console::{Key, Term};
 
 use super::hooks::Hooks;
+use super::history::{self, HistoryEntry};
+use super::keymap::EditAction;
+use super::killring::{self, KillRing};
+use super::render::{PromptLabels, Renderer};
+use super::CliHistorySettings;
 
+// Outcome of reading a single line
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReadResult {
+    // The line submitted with Enter, possibly empty
+    Line(String),
+    // Ctrl-D on an empty line or the end of stdin
+    Eof,
+}
+
+#[derive(Clone)]
 struct CharCollection {
     data: Vec<char>,
     idx: usize, 
@@ -40,87 +55,470 @@ impl CharCollection {
         }
     }
 
+    fn insert(&mut self, c: char) {
+        self.data.insert(self.idx, c);
+        self.idx += 1;
+    }
+
+    fn insert_str(&mut self, s: &str) {
+        // Insert everything at once, e.g. pasted text
+        let chars: Vec<char> = s.chars().collect();
+        let len = chars.len();
+
+        self.data.splice(self.idx..self.idx, chars);
+        self.idx += len;
+    }
+
     fn del_last(&mut self) {
         if self.idx > 0 {
             self.data.remove(self.idx - 1);
             self.idx -= 1;
         }
     } 
+
+    fn del_next(&mut self) {
+        if self.idx < self.data.len() {
+            self.data.remove(self.idx);
+        }
+    }
+
+    // Remove the chars in `range` and put the cursor where they were
+    fn cut(&mut self, range: std::ops::Range<usize>) -> String {
+        self.idx = range.start;
+        self.data.drain(range).collect()
+    }
+
+    // Start of the word left of the cursor
+    fn word_left(&self) -> usize {
+        let mut idx = self.idx;
+
+        while idx > 0 && self.data[idx - 1].is_whitespace() {
+            idx -= 1;
+        }
+        while idx > 0 && !self.data[idx - 1].is_whitespace() {
+            idx -= 1;
+        }
+
+        idx
+    }
+
+    // End of the word right of the cursor
+    fn word_right(&self) -> usize {
+        let mut idx = self.idx;
+
+        while idx < self.data.len() && self.data[idx].is_whitespace() {
+            idx += 1;
+        }
+        while idx < self.data.len() && !self.data[idx].is_whitespace() {
+            idx += 1;
+        }
+
+        idx
+    }
+}
+
+// Kind of an edit, consecutive edits of the same kind are undone in one step
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EditKind {
+    Typing,
+    Deleting,
+    Other, // Always a step of its own, e.g. a paste
+}
+
+// Buffer states to go back (undo) and forth (redo) to, including the cursor position
+struct UndoHistory {
+    undo: Vec<CharCollection>,
+    redo: Vec<CharCollection>,
+    group: Option<EditKind>, // Kind of the step the next edit may be merged into
+}
+
+impl UndoHistory {
+    fn new() -> Self {
+        UndoHistory {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            group: None
+        }
+    }
+
+    // Record a key press which turned `before` into `after`
+    fn track(&mut self, before: &CharCollection, after: &CharCollection, kind: EditKind) {
+        if before.data == after.data {
+            if before.idx != after.idx {
+                // Moving the cursor ends the current group
+                self.group = None;
+            }
+            return;
+        }
+
+        if kind == EditKind::Other || self.group != Some(kind) {
+            self.undo.push(before.clone());
+        }
+
+        self.redo.clear();
+        self.group = (kind != EditKind::Other).then_some(kind);
+    }
+
+    fn undo(&mut self, current: &mut CharCollection) {
+        if let Some(previous) = self.undo.pop() {
+            self.redo.push(std::mem::replace(current, previous));
+        }
+        self.group = None;
+    }
+
+    fn redo(&mut self, current: &mut CharCollection) {
+        if let Some(next) = self.redo.pop() {
+            self.undo.push(std::mem::replace(current, next));
+        }
+        self.group = None;
+    }
+}
+
+// Lines left while browsing the history, together with their undo steps. The slot after
+// the last entry holds the draft, so nothing typed is lost by looking at older commands.
+// Edits to recalled entries are kept until the line is submitted, the history itself never changes.
+struct HistoryEdits {
+    saved: Vec<Option<(CharCollection, UndoHistory)>>,
+}
+
+impl HistoryEdits {
+    fn new(history_len: usize) -> Self {
+        HistoryEdits {
+            saved: (0..=history_len).map(|_| None).collect()
+        }
+    }
+
+    // Put the line being edited aside as line `from` and continue with line `to`
+    fn switch(&mut self, history: &[HistoryEntry], from: usize, to: usize, collection: &mut CharCollection, undo: &mut UndoHistory) {
+        let (line, steps) = self.saved[to].take().unwrap_or_else(|| {
+            let mut line = CharCollection::new();
+            line.insert_str(history.get(to).map_or("", |entry| entry.command.as_str()));
+            (line, UndoHistory::new()) // Undo leads back to the entry as recalled
+        });
+
+        self.saved[from] = Some((std::mem::replace(collection, line), std::mem::replace(undo, steps)));
+    }
+
+    // Move the lines put aside from the entries of `old` to the same entries in `new`,
+    // lines of entries `new` doesn't hold anymore are dropped
+    fn rebase(&mut self, old: &[HistoryEntry], new: &[HistoryEntry]) {
+        let mut saved: Vec<Option<(CharCollection, UndoHistory)>> = (0..=new.len()).map(|_| None).collect();
+        saved[new.len()] = self.saved.pop().flatten(); // The draft stays the draft
+
+        for (entry, line) in old.iter().zip(self.saved.drain(..)) {
+            if let Some(line) = line
+                && let Some(idx) = new.iter().position(|known| history::same_entry(known, entry)) {
+                saved[idx] = Some(line);
+            }
+        }
+
+        self.saved = saved;
+    }
+}
+
+// Result of completing the command name left of the cursor
+#[derive(Debug, PartialEq)]
+enum Completion {
+    Insert(String), // Text completing the name (further)
+    Menu(Vec<String>), // Nothing to add, these names are possible
+    Nothing,
 }
 
-fn cursor_manage(collection: &mut CharCollection, cursor: char) {
-    collection.data.retain(|&c| c != cursor); // Remove previous cursor from input vector
-                
-    if collection.idx <= collection.data.len() { 
-        // Insert new cursor in new position
-        collection.data.insert(collection.idx.clone(), cursor);
+fn complete_command(data: &[char], idx: usize, commands: &[String]) -> Completion {
+    if data[..idx].iter().any(|c| c.is_whitespace()) {
+        // Only the first word names a command
+        return Completion::Nothing;
     }
+
+    let prefix: String = data[..idx].iter().collect();
+    let matches: Vec<&String> = commands.iter().filter(|name| name.starts_with(&prefix)).collect();
+
+    match matches.as_slice() {
+        [] => Completion::Nothing,
+        [name] => Completion::Insert(format!("{} ", &name[prefix.len()..])),
+        [first, rest @ ..] => {
+            // Longest prefix all matches have in common
+            let common = rest.iter().fold(first.len(), |len, name| {
+                first.chars().zip(name.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a.len_utf8()).sum::<usize>().min(len)
+            });
+
+            if common > prefix.len() {
+                Completion::Insert(first[prefix.len()..common].to_string())
+            } else {
+                Completion::Menu(matches.iter().map(|name| name.to_string()).collect())
+            }
+        }
+    }
+}
+
+// What the editor works with besides the keys
+pub(crate) struct PromptContext<'a> {
+    pub(crate) history: Vec<HistoryEntry>, // Browsed with the arrow keys, oldest first
+    pub(crate) start: usize, // Entry of `history` to edit first, history.len() for a new line
+    pub(crate) commands: &'a [String], // Names Tab completes the first word from
+    pub(crate) kill_ring: &'a mut KillRing, // Outlives the prompt, text killed in one line can be yanked in the next
+    pub(crate) sync: &'a mut dyn FnMut() -> Option<Vec<HistoryEntry>>, // Newer history shared by other sessions, if any
 }
 
-pub(crate) fn prompt(label: String, last_char: char) -> Option<String> {
+// Edit a single line starting with `initial`
+pub(crate) fn prompt(term: &Term, renderer: &mut Renderer, settings: &CliHistorySettings, labels: &PromptLabels, 
+    initial: &str, ctx: PromptContext) -> io::Result<ReadResult> {
+    let PromptContext { mut history, start, commands, kill_ring, sync } = ctx;
     let mut collection = CharCollection::new();
-    let mut term = Term::stdout();
-    let mut command: String = String::new();
+    let bindings = settings.key_bindings();
+    let mut history_idx = history.len(); // history.len() stands for the line being entered
+    let mut menu: Vec<String> = Vec::new();
+    let mut undo = UndoHistory::new();
+    let mut edits = HistoryEdits::new(history.len());
+    let mut last_action: Option<EditAction> = None;
+    let mut yanked = 0..0; // Chars inserted by the last yank, replaced by Alt-Y
 
-    term.hide_cursor().unwrap();
-    let cursor = '|';
+    collection.insert_str(initial);
 
-    collection.data.insert(0, last_char);
-    collection.idx += 1;
+    if start < history.len() {
+        // `initial` becomes the draft below the newest entry
+        edits.switch(&history, history_idx, start, &mut collection, &mut undo);
+        history_idx = start;
+    }
 
     loop {
-        let key = match term.read_key() {
-            Ok(key) => key,
-            Err(_) => {
-                return None;
-            }
+        renderer.draw_menu(term, labels, &collection.data, collection.idx, &menu)?;
+        menu.clear(); // Shown until the next key
+
+        let key = term.read_key()?;
+
+        if key == Key::Char('\x04') && collection.data.is_empty() {
+            // Ctrl-D on an empty line
+            renderer.finish(term)?;
+            return Ok(ReadResult::Eof);
+        }
+
+        let before = collection.clone();
+        let mut kind = EditKind::Other;
+        let action = bindings.get(&key);
+        let previous = std::mem::replace(&mut last_action, action);
+
+        // Kills following each other grow a single entry of the kill ring
+        let killed = match action {
+            Some(EditAction::KillLine) => Some((collection.cut(collection.idx..collection.data.len()), false)),
+            Some(EditAction::KillLineStart) => Some((collection.cut(0..collection.idx), true)),
+            Some(EditAction::KillWordBackward) => Some((collection.cut(collection.word_left()..collection.idx), true)),
+            Some(EditAction::KillWordForward) => Some((collection.cut(collection.idx..collection.word_right()), false)),
+            _ => None
         };
 
-        if key == Key::Char(' ') {
-            // Insert space
-            collection.data.insert(collection.idx, ' ');
-            collection.idx += 1;
-        } else if let Some(c) = Hooks::get_char(key.clone()) {
-            collection.data.insert(collection.idx, c);
-            collection.idx += 1;
-        } else if key == Key::ArrowLeft {
-            if collection.idx > 0 {
-                collection.idx -= 1;
-
-                cursor_manage(&mut collection, cursor);
-                term.move_cursor_left(1).unwrap(); // Move to the next char
+        if let Some((text, backward)) = killed {
+            let append = previous.is_some_and(EditAction::is_kill);
+            kill_ring.kill(&text, append, backward);
+
+            if settings.osc52_clipboard && !text.is_empty()
+                && let Some(latest) = kill_ring.latest() {
+                term.write_str(&killring::osc52(latest))?;
+            }
+        }
+
+        match action {
+            Some(EditAction::MoveLeft) => collection.idx = collection.idx.saturating_sub(1),
+            Some(EditAction::MoveRight) => collection.idx = (collection.idx + 1).min(collection.data.len()),
+            Some(EditAction::MoveWordLeft) => collection.idx = collection.word_left(),
+            Some(EditAction::MoveWordRight) => collection.idx = collection.word_right(),
+            Some(EditAction::MoveHome) => collection.idx = 0,
+            Some(EditAction::MoveEnd) => collection.idx = collection.data.len(),
+            Some(EditAction::DeleteBackward) => {
+                collection.del_last();
+                kind = EditKind::Deleting;
+            }
+            Some(EditAction::DeleteForward) => {
+                collection.del_next();
+                kind = EditKind::Deleting;
+            }
+            Some(EditAction::KillLine | EditAction::KillLineStart 
+                | EditAction::KillWordBackward | EditAction::KillWordForward) => {}
+            Some(EditAction::Yank) => {
+                if let Some(text) = kill_ring.yank() {
+                    let start = collection.idx;
+                    collection.insert_str(text);
+                    yanked = start..collection.idx;
+                }
+            }
+            Some(EditAction::YankRotate) => {
+                // Only right after a yank, replace what it inserted by the previous kill
+                if matches!(previous, Some(EditAction::Yank | EditAction::YankRotate))
+                    && let Some(text) = kill_ring.rotate() {
+                    collection.cut(yanked.clone());
+                    let start = collection.idx;
+                    collection.insert_str(text);
+                    yanked = start..collection.idx;
+                } else {
+                    last_action = None;
+                }
+            }
+            Some(EditAction::Undo) => {
+                undo.undo(&mut collection);
+                continue;
+            }
+            Some(EditAction::Redo) => {
+                undo.redo(&mut collection);
+                continue;
+            }
+            Some(EditAction::HistoryUp) => {
+                if history_idx == history.len()
+                    && let Some(shared) = sync() {
+                    // Other sessions added commands, they come before the line being entered
+                    edits.rebase(&history, &shared);
+                    history = shared;
+                    history_idx = history.len();
+                }
+
+                if history_idx > 0 {
+                    edits.switch(&history, history_idx, history_idx - 1, &mut collection, &mut undo);
+                    history_idx -= 1;
+                }
+                continue;
+            }
+            Some(EditAction::HistoryDown) => {
+                if history_idx < history.len() {
+                    edits.switch(&history, history_idx, history_idx + 1, &mut collection, &mut undo);
+                    history_idx += 1;
+                }
+                continue;
             }
-        } else if key == Key::ArrowRight {
-            if collection.idx < collection.data.len() {
-                collection.idx += 1;        
+            Some(EditAction::Complete) => match complete_command(&collection.data, collection.idx, commands) {
+                Completion::Insert(text) => collection.insert_str(&text),
+                Completion::Menu(names) => menu = names,
+                Completion::Nothing => {}
+            },
+            Some(EditAction::Submit) => {
+                // Show the complete command and leave the prompt rows behind
+                renderer.draw(term, labels, &collection.data, collection.data.len())?;
+                renderer.finish(term)?;
 
-                cursor_manage(&mut collection, cursor);
-                term.move_cursor_right(1).unwrap();
+                break;
+            }
+            None => {
+                if Hooks::is_paste_start(term, &key) {
+                    let pasted = Hooks::read_paste(term, settings.max_paste_size, settings.keep_paste_newlines);
+                    collection.insert_str(&pasted);
+                } else if let Some(c) = Hooks::get_char(key) {
+                    collection.insert(c);
+                    kind = EditKind::Typing;
+                }
             }
-        } else if key == Key::Backspace {
-            collection.del_last();
         }
 
-        command.clear();
-        term.clear_line().unwrap();
+        undo.track(&before, &collection, kind);
+    }
+
+    Ok(ReadResult::Line(collection.data.iter().collect::<String>()))
+}
+
+// Without an attended terminal read_key() never yields anything but Key::Unknown,
+// so commands are read line by line from stdin instead. Returns None on EOF.
+pub(crate) fn prompt_unattended() -> Option<String> {
+    let mut line = String::new();
+
+    match io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        command = collection.data.iter().collect::<String>();
-        term.write(&format!("{} {}", label, command).as_bytes()).unwrap();
+    #[test]
+    fn test_complete_command() {
+        let commands: Vec<String> = ["help", "history", "list", "load"].iter().map(|name| name.to_string()).collect();
+        let complete = |line: &str| {
+            let data: Vec<char> = line.chars().collect();
+            complete_command(&data, data.len(), &commands)
+        };
 
-        term.flush().unwrap();
+        assert_eq!(complete("li"), Completion::Insert("st ".to_string()));
+        assert_eq!(complete("h"), Completion::Menu(vec!["help".to_string(), "history".to_string()]));
+        assert_eq!(complete("hi"), Completion::Insert("story ".to_string()));
+        assert_eq!(complete(""), Completion::Menu(commands.clone()));
+        assert_eq!(complete("list x"), Completion::Nothing);
+        assert_eq!(complete("q"), Completion::Nothing);
+    }
 
-        if key == Key::Enter {
-            // Remove cursor from command after enter
-            collection.data.retain(|&c| c != cursor);
-            command = collection.data.iter().collect::<String>();
-            term.write_line(&format!("\r{} {} ", label, command)).unwrap();
+    #[test]
+    fn test_undo_groups() {
+        let mut collection = CharCollection::new();
+        let mut undo = UndoHistory::new();
+        let edit = |collection: &mut CharCollection, undo: &mut UndoHistory, kind, change: &dyn Fn(&mut CharCollection)| {
+            let before = collection.clone();
+            change(collection);
+            undo.track(&before, collection, kind);
+        };
 
-            break;
+        for c in "abc".chars() {
+            edit(&mut collection, &mut undo, EditKind::Typing, &|collection| collection.insert(c));
         }
+        edit(&mut collection, &mut undo, EditKind::Other, &|collection| collection.idx = 1);
+        edit(&mut collection, &mut undo, EditKind::Typing, &|collection| collection.insert('x'));
+        edit(&mut collection, &mut undo, EditKind::Deleting, &|collection| collection.del_next());
+        assert_eq!(collection.data.iter().collect::<String>(), "axc");
+
+        undo.undo(&mut collection);
+        assert_eq!((collection.data.iter().collect::<String>(), collection.idx), ("axbc".to_string(), 2));
+        undo.undo(&mut collection);
+        assert_eq!((collection.data.iter().collect::<String>(), collection.idx), ("abc".to_string(), 1));
+        undo.undo(&mut collection);
+        assert!(collection.data.is_empty());
+
+        undo.redo(&mut collection);
+        assert_eq!(collection.data.iter().collect::<String>(), "abc");
     }
 
-    Some(command)
-}
+    #[test]
+    fn test_history_edits() {
+        let history = vec![HistoryEntry::new("ls", ""), HistoryEntry::new("make", "")];
+        let mut edits = HistoryEdits::new(history.len());
+        let mut collection = CharCollection::new();
+        let mut undo = UndoHistory::new();
+        let text = |collection: &CharCollection| collection.data.iter().collect::<String>();
+
+        collection.insert_str("git st");
+        edits.switch(&history, 2, 1, &mut collection, &mut undo);
+        assert_eq!(text(&collection), "make");
 
+        let before = collection.clone();
+        collection.insert_str(" test");
+        undo.track(&before, &collection, EditKind::Other);
+        edits.switch(&history, 1, 0, &mut collection, &mut undo);
+        assert_eq!(text(&collection), "ls");
 
+        edits.switch(&history, 0, 1, &mut collection, &mut undo);
+        assert_eq!(text(&collection), "make test");
+        edits.switch(&history, 1, 2, &mut collection, &mut undo);
+        assert_eq!((text(&collection), collection.idx), ("git st".to_string(), 6));
+
+        edits.switch(&history, 2, 1, &mut collection, &mut undo);
+        undo.undo(&mut collection);
+        assert_eq!(text(&collection), "make");
+    }
+
+    #[test]
+    fn test_history_edits_rebase() {
+        let history = vec![HistoryEntry::new("ls", ""), HistoryEntry::new("make", "")];
+        let shared = vec![history[0].clone(), HistoryEntry::new("cd src", "b"), history[1].clone()];
+        let mut edits = HistoryEdits::new(history.len());
+        let mut collection = CharCollection::new();
+        let mut undo = UndoHistory::new();
+
+        edits.switch(&history, 2, 1, &mut collection, &mut undo);
+        collection.insert_str(" test");
+        edits.switch(&history, 1, 2, &mut collection, &mut undo);
+        collection.insert_str("git st");
+
+        edits.rebase(&history, &shared);
+        edits.switch(&shared, 3, 2, &mut collection, &mut undo);
+        assert_eq!(collection.data.iter().collect::<String>(), "make test");
+        edits.switch(&shared, 2, 1, &mut collection, &mut undo);
+        assert_eq!(collection.data.iter().collect::<String>(), "cd src");
+        edits.switch(&shared, 1, 3, &mut collection, &mut undo);
+        assert_eq!(collection.data.iter().collect::<String>(), "git st");
+    }
+}
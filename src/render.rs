@@ -0,0 +1,328 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use console::{measure_text_width, Term};
+
+// Width of a single char as the terminal will display it
+fn char_width(c: char) -> usize {
+    let mut buf = [0u8; 4];
+    measure_text_width(c.encode_utf8(&mut buf))
+}
+
+// Screen position (row, column) relative to the first prompt row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Position {
+    pub(crate) row: usize,
+    pub(crate) col: usize,
+}
+
+impl Position {
+    // Position after the prompt label was written
+    fn after_label(label_width: usize, width: usize) -> Self {
+        Position {
+            row: label_width / width,
+            col: label_width % width
+        }
+    }
+
+    // Move the position forward like the terminal does when printing `c`
+    fn advance(&mut self, c: char, width: usize) {
+        if c == '\n' {
+            self.row += 1;
+            self.col = 0;
+            return;
+        }
+
+        let cw = char_width(c);
+        if self.col + cw > width {
+            // Wide chars which don't fit are moved to the next row
+            self.row += 1;
+            self.col = 0;
+        }
+
+        self.col += cw;
+        if self.col >= width {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+}
+
+// Calculate the position of the cursor after printing `label` and the first `idx` chars of `buffer`
+pub(crate) fn locate(label_width: usize, buffer: &[char], idx: usize, width: usize) -> Position {
+    let width = width.max(1);
+    let mut pos = Position::after_label(label_width, width);
+
+    for &c in buffer.iter().take(idx) {
+        pos.advance(c, width);
+    }
+
+    pos
+}
+
+// Interval used by the resize watcher to poll the terminal size
+const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Left and right (RPROMPT like) label of a prompt line
+#[derive(Clone, Default)]
+pub(crate) struct PromptLabels {
+    pub(crate) left: String,
+    pub(crate) right: String,
+}
+
+// Everything needed to draw the prompt again
+struct Frame {
+    labels: PromptLabels,
+    buffer: Vec<char>,
+    idx: usize,
+    menu: Vec<String>, // Completion candidates listed below the input
+}
+
+// Terminal rows occupied by the last draw
+struct Screen {
+    rows: usize, // Rows used by the last draw
+    cursor_row: usize, // Row of the cursor relative to the first prompt row
+    width: usize, // Terminal width used for the last draw
+    frame: Option<Frame>, // Prompt currently displayed, None if no prompt is active
+}
+
+impl Screen {
+    fn paint(&mut self, term: &Term, width: usize) -> io::Result<()> {
+        let frame = match self.frame {
+            Some(ref frame) => frame,
+            None => return Ok(())
+        };
+
+        let prefix = format!("{} ", frame.labels.left);
+        let label_width = measure_text_width(&prefix);
+        let mut out = String::new();
+
+        if width != self.width {
+            // The terminal reflowed the rows we drew, find the cursor row in the new layout
+            self.cursor_row = locate(label_width, &frame.buffer, frame.idx, width).row;
+        }
+
+        // Go back to the first prompt row and wipe everything drawn before
+        if self.cursor_row > 0 {
+            out.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        out.push_str("\r\x1b[J");
+
+        out.push_str(&prefix);
+        for &c in &frame.buffer {
+            if c == '\n' {
+                out.push_str("\r\n");
+            } else {
+                out.push(c);
+            }
+        }
+
+        let end = locate(label_width, &frame.buffer, frame.buffer.len(), width);
+        let wrapped = end.col == 0 && end.row > 0 && frame.buffer.last() != Some(&'\n');
+        if wrapped {
+            // The last char filled the row completely, force the terminal
+            // to wrap so the cursor ends up where we expect it to be
+            out.push_str("\r\n");
+        }
+
+        // The right label is only shown as long as the input doesn't grow into it
+        let right_width = measure_text_width(&frame.labels.right);
+        if right_width > 0 && end.row == 0 && end.col + 1 + right_width < width {
+            out.push_str(&format!("\r\x1b[{}C{}", width - 1 - right_width, frame.labels.right));
+        }
+
+        let mut last_row = end.row;
+        if !frame.menu.is_empty() {
+            let menu = frame.menu.join("  ");
+            let menu_chars: Vec<char> = menu.chars().collect();
+            let menu_end = locate(0, &menu_chars, menu_chars.len(), width);
+
+            // After a forced wrap the cursor already is on the empty row below the input
+            let menu_row = if wrapped { end.row } else { end.row + 1 };
+            if !wrapped {
+                out.push_str("\r\n");
+            }
+            out.push_str(&menu);
+            if menu_end.col == 0 && menu_end.row > 0 {
+                out.push_str("\r\n");
+            }
+
+            last_row = menu_row + menu_end.row;
+        }
+
+        // Place the cursor on the row/column of the buffer index
+        let cursor = locate(label_width, &frame.buffer, frame.idx, width);
+        if last_row > cursor.row {
+            out.push_str(&format!("\x1b[{}A", last_row - cursor.row));
+        }
+        out.push('\r');
+        if cursor.col > 0 {
+            out.push_str(&format!("\x1b[{}C", cursor.col));
+        }
+
+        self.rows = last_row + 1;
+        self.cursor_row = cursor.row;
+        self.width = width;
+
+        term.write_str(&out)?;
+        term.flush()
+    }
+}
+
+// Current terminal width in columns
+fn term_width(term: &Term) -> usize {
+    term.size().1.max(1) as usize
+}
+
+// Draws the prompt label and the input buffer, keeping track of
+// the terminal rows occupied by the last draw. While a prompt is
+// displayed a watcher thread re-layouts it when the terminal is resized.
+pub(crate) struct Renderer {
+    screen: Arc<Mutex<Screen>>,
+    watcher: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl Renderer {
+    pub(crate) fn new(term: &Term) -> Self {
+        let screen = Arc::new(Mutex::new(Screen {
+            rows: 1,
+            cursor_row: 0,
+            width: term_width(term),
+            frame: None
+        }));
+
+        let mut renderer = Renderer {
+            screen,
+            watcher: None
+        };
+
+        if term.is_term() {
+            renderer.spawn_watcher(term.clone());
+        }
+
+        renderer
+    }
+
+    // Watch for terminal size changes (SIGWINCH) while read_key() blocks the
+    // editing thread. Installing a real signal handler is not an option here:
+    // console treats an interrupted read as Ctrl-C and raises SIGINT.
+    fn spawn_watcher(&mut self, term: Term) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let screen = Arc::clone(&self.screen);
+        let stop_watcher = Arc::clone(&stop);
+
+        let handle = thread::spawn(move || {
+            while !stop_watcher.load(Ordering::Relaxed) {
+                thread::park_timeout(RESIZE_POLL_INTERVAL);
+
+                let width = term_width(&term);
+                if let Ok(mut screen) = screen.lock()
+                    && screen.width != width {
+                    let _ = screen.paint(&term, width);
+                }
+            }
+        });
+
+        self.watcher = Some((stop, handle));
+    }
+
+    fn screen(&self) -> MutexGuard<'_, Screen> {
+        self.screen.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn draw(&mut self, term: &Term, labels: &PromptLabels, buffer: &[char], idx: usize) -> io::Result<()> {
+        self.draw_menu(term, labels, buffer, idx, &[])
+    }
+
+    // Draw the prompt with a menu of completion candidates below it
+    pub(crate) fn draw_menu(&mut self, term: &Term, labels: &PromptLabels, buffer: &[char], idx: usize, menu: &[String]) -> io::Result<()> {
+        let mut screen = self.screen();
+
+        screen.frame = Some(Frame {
+            labels: labels.clone(),
+            buffer: buffer.to_vec(),
+            idx,
+            menu: menu.to_vec()
+        });
+
+        screen.paint(term, term_width(term))
+    }
+
+    // Move below the last prompt row and start over with a fresh line
+    pub(crate) fn finish(&mut self, term: &Term) -> io::Result<()> {
+        let mut screen = self.screen();
+        let mut out = String::new();
+        let below = screen.rows.saturating_sub(screen.cursor_row + 1);
+
+        if below > 0 {
+            out.push_str(&format!("\x1b[{}B", below));
+        }
+        out.push_str("\r\n");
+
+        screen.rows = 1;
+        screen.cursor_row = 0;
+        screen.frame = None; // Nothing to re-layout until the next draw
+
+        term.write_str(&out)?;
+        term.flush()
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        if let Some((stop, handle)) = self.watcher.take() {
+            stop.store(true, Ordering::Relaxed);
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_wrapping() {
+        let buffer: Vec<char> = "abcdefgh".chars().collect();
+
+        assert_eq!(locate(2, &buffer, 0, 5), Position { row: 0, col: 2 });
+        assert_eq!(locate(2, &buffer, 3, 5), Position { row: 1, col: 0 });
+        assert_eq!(locate(2, &buffer, 8, 5), Position { row: 2, col: 0 });
+    }
+
+    #[test]
+    fn test_locate_wide_and_newline() {
+        let buffer: Vec<char> = "ab漢\ncd".chars().collect();
+
+        // The wide char doesn't fit into the last column and wraps
+        assert_eq!(locate(2, &buffer, 3, 5), Position { row: 1, col: 2 });
+        assert_eq!(locate(2, &buffer, 6, 5), Position { row: 2, col: 2 });
+    }
+}
@@ -0,0 +1,557 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use console::Key;
+
+use super::keymap::{EditAction, EditMode, KeyBindings};
+use super::label::Label;
+use super::format::LogFormat;
+use super::history::HistoryEntry;
+use super::redact::{Redactor, Redactors, RegexRedactor};
+use super::alias::{expand_alias, expand_variables};
+
+#[derive(Clone, Debug)]
+pub struct CliHistorySettings {
+    pub(crate) label: Label,
+    pub(crate) right_label: Label,
+    pub(crate) max_size: usize,
+    pub(crate) max_size_log_file: usize,
+    pub(crate) die_on_exit: bool,
+    pub(crate) log_file_path: String,
+    pub(crate) log_format: LogFormat,
+    pub(crate) share_history: bool, // Pick up commands of other sessions writing the same history
+    pub(crate) ignore_dups: bool, // Skip a command equal to the previous one
+    pub(crate) erase_dups: bool, // Remove older entries equal to a new command
+    pub(crate) ignore_space: bool, // Skip commands starting with a space
+    pub(crate) ignore_patterns: Vec<String>, // Skip commands matching one of these patterns
+    pub(crate) redact_secrets: bool, // Apply the built-in redactors
+    pub(crate) redactors: Redactors,
+    pub(crate) keep_raw_in_memory: bool, // Keep the in-memory history entry unredacted
+    pub(crate) history_expansion: bool, // Expand !!, !n, ^old^new, ...
+    pub(crate) confirm_expansion: bool, // Load expanded lines into the editor instead of running them
+    pub(crate) history_builtins: bool, // Handle `history` and `fc` in the navigator
+    pub(crate) aliases: BTreeMap<String, String>,
+    pub(crate) alias_builtins: bool, // Handle `alias` and `unalias` in the navigator
+    pub(crate) variables: BTreeMap<String, String>, // Looked up before the environment
+    pub(crate) variable_expansion: bool, // Expand $NAME and ${NAME}
+    #[cfg(feature = "sqlite")]
+    pub(crate) sqlite_path: String,
+    pub(crate) max_paste_size: usize,
+    pub(crate) keep_paste_newlines: bool,
+    pub(crate) osc52_clipboard: bool, // Copy killed text to the system clipboard
+    pub(crate) exit_words: Vec<String>,
+    pub(crate) edit_mode: EditMode,
+    pub(crate) custom_bindings: KeyBindings, // Applied on top of the edit mode defaults
+}
+
+impl CliHistorySettings {
+    pub fn new() -> Self {
+        CliHistorySettings { 
+            label: Label::from("CliHistoryPrompt: "),
+            right_label: Label::new(),
+            max_size: 500,
+            max_size_log_file: 500,
+            die_on_exit: false,
+            log_file_path: String::new(),
+            log_format: LogFormat::default(),
+            share_history: false,
+            ignore_dups: false,
+            erase_dups: false,
+            ignore_space: false,
+            ignore_patterns: Vec::new(),
+            redact_secrets: false,
+            redactors: Redactors::default(),
+            keep_raw_in_memory: false,
+            history_expansion: false,
+            confirm_expansion: false,
+            history_builtins: false,
+            aliases: BTreeMap::new(),
+            alias_builtins: false,
+            variables: BTreeMap::new(),
+            variable_expansion: false,
+            #[cfg(feature = "sqlite")]
+            sqlite_path: String::new(),
+            max_paste_size: 65536,
+            keep_paste_newlines: false,
+            osc52_clipboard: false,
+            exit_words: vec!["exit".to_string()],
+            edit_mode: EditMode::default(),
+            custom_bindings: KeyBindings::new(),
+        }
+    }
+
+    pub fn builder() -> CliHistorySettingsBuilder {
+        CliHistorySettingsBuilder::new()
+    }
+
+    pub fn set_label<L: Into<Label>>(&mut self, label: L) {
+        self.label = label.into();
+    }
+
+    pub fn set_right_label<L: Into<Label>>(&mut self, label: L) {
+        self.right_label = label.into();
+    }
+
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    pub fn set_die_on_exit(&mut self) {
+        self.die_on_exit = true;
+    }
+
+    pub fn set_max_size_log_file(&mut self, max_size: usize) {
+        self.max_size_log_file = max_size
+    } 
+
+    pub fn set_log_to_file(&mut self, file_path: &str) {
+        self.log_file_path = file_path.to_string();
+    } 
+
+    // Plain text by default, JSON Lines keeps the metadata of every command
+    pub fn set_log_format(&mut self, format: LogFormat) {
+        self.log_format = format;
+    }
+
+    // Commands written to the history by other sessions show up when navigating, like zsh's SHARE_HISTORY
+    pub fn set_share_history(&mut self) {
+        self.share_history = true;
+    }
+
+    // Don't record a command equal to the previous one (HISTCONTROL=ignoredups)
+    pub fn set_ignore_dups(&mut self) {
+        self.ignore_dups = true;
+    }
+
+    // Remove all older entries equal to a new command from the history (HISTCONTROL=erasedups)
+    pub fn set_erase_dups(&mut self) {
+        self.erase_dups = true;
+    }
+
+    // Don't record commands starting with a space (HISTCONTROL=ignorespace)
+    pub fn set_ignore_space(&mut self) {
+        self.ignore_space = true;
+    }
+
+    // Don't record commands matching one of the patterns (HISTIGNORE). A pattern has to match
+    // the whole command, `*` matches any text and `?` a single char: &["ls", "ls *", "clear"]
+    pub fn set_ignore_patterns(&mut self, patterns: &[&str]) {
+        self.ignore_patterns = patterns.iter().map(|pattern| pattern.to_string()).collect();
+    }
+
+    // Replace passwords, bearer tokens and AWS keys with [REDACTED] before commands are recorded
+    pub fn set_redact_secrets(&mut self) {
+        self.redact_secrets = true;
+    }
+
+    // Redactors run after the built-in ones, in the order they were added
+    pub fn add_redactor<R: Redactor + 'static>(&mut self, redactor: R) {
+        self.redactors.push(Arc::new(redactor));
+    }
+
+    // Replace every match of `pattern` with `replacement` ("${1}***" keeps the first group)
+    pub fn add_redaction_rule(&mut self, pattern: &str, replacement: &str) -> Result<(), regex::Error> {
+        self.add_redactor(RegexRedactor::new(pattern, replacement)?);
+        Ok(())
+    }
+
+    // Keep entries unredacted in the in-memory history. The store still only ever
+    // receives the redacted line.
+    pub fn set_keep_raw_in_memory(&mut self) {
+        self.keep_raw_in_memory = true;
+    }
+
+    // Expand bash style history references in submitted lines: !!, !n, !-n, !string, 
+    // !?string?, !$, !*, word designators like !!:2 and ^old^new quick substitution
+    pub fn set_history_expansion(&mut self) {
+        self.history_expansion = true;
+    }
+
+    // Show an expanded line in the editor again so it runs only after a second Enter (zsh HIST_VERIFY)
+    pub fn set_confirm_expansion(&mut self) {
+        self.confirm_expansion = true;
+    }
+
+    // Let the navigator handle the built-in commands `history [n]`, `history -c`, `history -d <n>`,
+    // `history -w`, `history -r` and `fc [n | -n | string]` instead of passing them to the callback
+    pub fn set_history_builtins(&mut self) {
+        self.history_builtins = true;
+    }
+
+    // Replace `name` at the start of a line with `value` before the line reaches the callback,
+    // the history keeps the line as entered
+    pub fn set_alias(&mut self, name: &str, value: &str) {
+        self.aliases.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove_alias(&mut self, name: &str) -> Option<String> {
+        self.aliases.remove(name)
+    }
+
+    pub fn aliases(&self) -> &BTreeMap<String, String> {
+        &self.aliases
+    }
+
+    // Let the navigator handle `alias`, `alias name=value` and `unalias name`
+    pub fn set_alias_builtins(&mut self) {
+        self.alias_builtins = true;
+    }
+
+    // Value of $name, takes precedence over the environment variable of the same name
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove_variable(&mut self, name: &str) -> Option<String> {
+        self.variables.remove(name)
+    }
+
+    // Expand $NAME and ${NAME} from the variables set above and the environment
+    pub fn set_variable_expansion(&mut self) {
+        self.variable_expansion = true;
+    }
+
+    // Keep the history in a SQLite database, the in-memory history becomes a cache of its newest entries
+    #[cfg(feature = "sqlite")]
+    pub fn set_sqlite_history(&mut self, db_path: &str) {
+        self.sqlite_path = db_path.to_string();
+    }
+
+    pub fn set_max_paste_size(&mut self, max_size: usize) {
+        self.max_paste_size = max_size;
+    }
+
+    pub fn set_keep_paste_newlines(&mut self) {
+        self.keep_paste_newlines = true;
+    }
+
+    // Also put killed text on the system clipboard with OSC 52, the terminal has to allow it
+    pub fn set_osc52_clipboard(&mut self) {
+        self.osc52_clipboard = true;
+    }
+
+    // Commands which stop the navigator when die_on_exit is set, default: "exit"
+    pub fn set_exit_words(&mut self, words: &[&str]) {
+        self.exit_words = words.iter().map(|word| word.to_string()).collect();
+    }
+
+    pub fn set_edit_mode(&mut self, mode: EditMode) {
+        self.edit_mode = mode;
+    }
+
+    pub fn bind_key(&mut self, key: Key, action: EditAction) {
+        self.custom_bindings.bind(key, action);
+    }
+
+    // Bindings of the edit mode merged with the custom ones
+    pub(crate) fn key_bindings(&self) -> KeyBindings {
+        let mut bindings = KeyBindings::for_mode(self.edit_mode);
+        bindings.extend(&self.custom_bindings);
+        bindings
+    }
+
+    pub(crate) fn is_exit_word(&self, input: &str) -> bool {
+        self.die_on_exit && self.exit_words.iter().any(|word| word == input)
+    }
+
+    // `entry` as it goes into the store, in-memory entries may still be raw
+    pub(crate) fn stored_entry(&self, entry: &HistoryEntry) -> HistoryEntry {
+        if self.keep_raw_in_memory {
+            HistoryEntry { command: self.redact(&entry.command), ..entry.clone() }
+        } else {
+            entry.clone()
+        }
+    }
+
+    pub(crate) fn redact(&self, input: &str) -> String {
+        let command = if self.redact_secrets {
+            Redactors::builtin().apply(input)
+        } else {
+            input.to_string()
+        };
+
+        self.redactors.apply(&command)
+    }
+
+    // Line handed to the callback: aliases and variables replaced
+    pub(crate) fn expand_command(&self, line: &str) -> String {
+        let line = expand_alias(line, &self.aliases).unwrap_or_else(|| line.to_string());
+
+        if !self.variable_expansion {
+            return line;
+        }
+
+        expand_variables(&line, |name| {
+            self.variables.get(name).cloned().or_else(|| std::env::var(name).ok())
+        })
+    }
+
+    // Commands which are never recorded, regardless of what was entered before
+    pub(crate) fn is_ignored(&self, input: &str) -> bool {
+        (self.ignore_space && input.starts_with(' '))
+            || self.ignore_patterns.iter().any(|pattern| glob_match(pattern, input))
+    }
+}
+
+// Match `text` against a shell like pattern with `*` and `?` wildcards
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None; // Position after the last `*` and the text it resumes at
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p + 1, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` swallow one more char
+            p = star_p;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl Default for CliHistorySettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&CliHistorySettings> for CliHistorySettings {
+    fn from(settings: &CliHistorySettings) -> Self {
+        settings.clone()
+    }
+}
+
+// Fluent way to put the settings together:
+// CliHistorySettings::builder().label("> ").max_size(100).build()
+#[derive(Clone, Debug, Default)]
+pub struct CliHistorySettingsBuilder {
+    settings: CliHistorySettings,
+}
+
+impl CliHistorySettingsBuilder {
+    pub fn new() -> Self {
+        CliHistorySettingsBuilder { 
+            settings: CliHistorySettings::new() 
+        }
+    }
+
+    pub fn label<L: Into<Label>>(mut self, label: L) -> Self {
+        self.settings.set_label(label);
+        self
+    }
+
+    pub fn right_label<L: Into<Label>>(mut self, label: L) -> Self {
+        self.settings.set_right_label(label);
+        self
+    }
+
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.settings.max_size = max_size;
+        self
+    }
+
+    pub fn die_on_exit(mut self, die_on_exit: bool) -> Self {
+        self.settings.die_on_exit = die_on_exit;
+        self
+    }
+
+    pub fn max_size_log_file(mut self, max_size: usize) -> Self {
+        self.settings.max_size_log_file = max_size;
+        self
+    }
+
+    pub fn log_to_file(mut self, file_path: &str) -> Self {
+        self.settings.set_log_to_file(file_path);
+        self
+    }
+
+    pub fn log_format(mut self, format: LogFormat) -> Self {
+        self.settings.log_format = format;
+        self
+    }
+
+    pub fn share_history(mut self, share: bool) -> Self {
+        self.settings.share_history = share;
+        self
+    }
+
+    pub fn ignore_dups(mut self, ignore: bool) -> Self {
+        self.settings.ignore_dups = ignore;
+        self
+    }
+
+    pub fn erase_dups(mut self, erase: bool) -> Self {
+        self.settings.erase_dups = erase;
+        self
+    }
+
+    pub fn ignore_space(mut self, ignore: bool) -> Self {
+        self.settings.ignore_space = ignore;
+        self
+    }
+
+    pub fn ignore_patterns(mut self, patterns: &[&str]) -> Self {
+        self.settings.set_ignore_patterns(patterns);
+        self
+    }
+
+    pub fn redact_secrets(mut self, redact: bool) -> Self {
+        self.settings.redact_secrets = redact;
+        self
+    }
+
+    pub fn redactor<R: Redactor + 'static>(mut self, redactor: R) -> Self {
+        self.settings.add_redactor(redactor);
+        self
+    }
+
+    pub fn keep_raw_in_memory(mut self, keep: bool) -> Self {
+        self.settings.keep_raw_in_memory = keep;
+        self
+    }
+
+    pub fn history_expansion(mut self, expand: bool) -> Self {
+        self.settings.history_expansion = expand;
+        self
+    }
+
+    pub fn confirm_expansion(mut self, confirm: bool) -> Self {
+        self.settings.confirm_expansion = confirm;
+        self
+    }
+
+    pub fn history_builtins(mut self, enable: bool) -> Self {
+        self.settings.history_builtins = enable;
+        self
+    }
+
+    pub fn alias(mut self, name: &str, value: &str) -> Self {
+        self.settings.set_alias(name, value);
+        self
+    }
+
+    pub fn alias_builtins(mut self, enable: bool) -> Self {
+        self.settings.alias_builtins = enable;
+        self
+    }
+
+    pub fn variable(mut self, name: &str, value: &str) -> Self {
+        self.settings.set_variable(name, value);
+        self
+    }
+
+    pub fn variable_expansion(mut self, expand: bool) -> Self {
+        self.settings.variable_expansion = expand;
+        self
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_history(mut self, db_path: &str) -> Self {
+        self.settings.set_sqlite_history(db_path);
+        self
+    }
+
+    pub fn max_paste_size(mut self, max_size: usize) -> Self {
+        self.settings.max_paste_size = max_size;
+        self
+    }
+
+    pub fn keep_paste_newlines(mut self, keep: bool) -> Self {
+        self.settings.keep_paste_newlines = keep;
+        self
+    }
+
+    pub fn osc52_clipboard(mut self, enable: bool) -> Self {
+        self.settings.osc52_clipboard = enable;
+        self
+    }
+
+    pub fn exit_words(mut self, words: &[&str]) -> Self {
+        self.settings.set_exit_words(words);
+        self
+    }
+
+    pub fn edit_mode(mut self, mode: EditMode) -> Self {
+        self.settings.edit_mode = mode;
+        self
+    }
+
+    pub fn bind_key(mut self, key: Key, action: EditAction) -> Self {
+        self.settings.bind_key(key, action);
+        self
+    }
+
+    pub fn build(self) -> CliHistorySettings {
+        self.settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let settings = CliHistorySettings::builder()
+            .max_size(100)
+            .die_on_exit(true)
+            .log_to_file("history.txt")
+            .build();
+
+        let copy = settings.clone();
+        assert_eq!(copy.max_size, 100);
+        assert!(copy.die_on_exit);
+        assert_eq!(copy.log_file_path, "history.txt");
+        assert_eq!(copy.max_size_log_file, CliHistorySettings::default().max_size_log_file);
+    }
+
+    #[test]
+    fn test_ignored_commands() {
+        let settings = CliHistorySettings::builder()
+            .ignore_space(true)
+            .ignore_patterns(&["ls", "ls *", "git p?sh"])
+            .build();
+
+        assert!(settings.is_ignored(" secret"));
+        assert!(settings.is_ignored("ls"));
+        assert!(settings.is_ignored("ls -la"));
+        assert!(settings.is_ignored("git push"));
+        assert!(!settings.is_ignored("lsblk"));
+        assert!(!settings.is_ignored("git pull"));
+        assert!(!settings.is_ignored("cargo build"));
+    }
+}
@@ -0,0 +1,321 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use rusqlite::{params, Connection, Row};
+
+use super::history::HistoryEntry;
+use super::store::HistoryStore;
+
+// How long a writer waits for another session holding the database lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Rows fetched at once when walking the history from the newest entry
+const PAGE_SIZE: usize = 256;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        command TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        duration_ms INTEGER,
+        exit_status INTEGER,
+        cwd TEXT,
+        session_id TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS history_command ON history(command);
+    CREATE INDEX IF NOT EXISTS history_timestamp ON history(timestamp);
+    CREATE INDEX IF NOT EXISTS history_session ON history(session_id);
+";
+
+const COLUMNS: &str = "command, timestamp, duration_ms, exit_status, cwd, session_id";
+
+// History kept in a SQLite database. The database runs in WAL mode so
+// several sessions can read and append to the same file at once.
+pub struct SqliteHistory {
+    conn: Connection,
+}
+
+fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<HistoryEntry> {
+    let timestamp: i64 = row.get(1)?;
+    let duration: Option<i64> = row.get(2)?;
+    let cwd: Option<String> = row.get(4)?;
+
+    Ok(HistoryEntry {
+        command: row.get(0)?,
+        timestamp: UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64),
+        duration: duration.map(|ms| Duration::from_millis(ms.max(0) as u64)),
+        exit_status: row.get(3)?,
+        cwd: cwd.map(PathBuf::from),
+        session_id: row.get(5)?,
+    })
+}
+
+// Walks the history newest first, one page at a time. Each page continues
+// below the smallest id seen so far, so rows appended meanwhile are skipped.
+struct NewestPages<'a> {
+    history: &'a SqliteHistory,
+    page: std::vec::IntoIter<(i64, HistoryEntry)>,
+    before: i64,
+    done: bool,
+}
+
+impl Iterator for NewestPages<'_> {
+    type Item = HistoryEntry;
+
+    fn next(&mut self) -> Option<HistoryEntry> {
+        loop {
+            if let Some((id, entry)) = self.page.next() {
+                self.before = id;
+                return Some(entry);
+            }
+
+            if self.done {
+                return None;
+            }
+
+            // A failing page ends the walk, the first one is checked up front
+            let page = self.history.page_before(self.before).unwrap_or_default();
+            self.done = page.len() < PAGE_SIZE;
+            self.page = page.into_iter();
+        }
+    }
+}
+
+impl SqliteHistory {
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        SqliteHistory::init(Connection::open(path)?)
+    }
+
+    // Database living in memory only, mostly useful for testing
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        SqliteHistory::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<Self> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(SqliteHistory { 
+            conn 
+        })
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> rusqlite::Result<()> {
+        self.conn.execute(
+            &format!("INSERT INTO history ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)", COLUMNS),
+            params![
+                entry.command,
+                entry.unix_timestamp() as i64,
+                entry.duration.map(|d| d.as_millis() as i64),
+                entry.exit_status,
+                entry.cwd.as_ref().map(|cwd| cwd.to_string_lossy().into_owned()),
+                entry.session_id,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> rusqlite::Result<usize> {
+        self.conn.query_row("SELECT COUNT(*) FROM history", [], |row| row.get::<_, i64>(0))
+            .map(|count| count as usize)
+    }
+
+    pub fn is_empty(&self) -> rusqlite::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    // Entry by position, 0 being the oldest one
+    pub fn get(&self, index: usize) -> rusqlite::Result<Option<HistoryEntry>> {
+        let mut stmt = self.conn.prepare_cached(&format!("SELECT {} FROM history ORDER BY id LIMIT 1 OFFSET ?1", COLUMNS))?;
+        let mut rows = stmt.query_map(params![index as i64], entry_from_row)?;
+
+        rows.next().transpose()
+    }
+
+    // The newest `limit` entries, oldest first
+    pub fn recent(&self, limit: usize) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM (SELECT id, {} FROM history ORDER BY id DESC LIMIT ?1) ORDER BY id", COLUMNS, COLUMNS))?;
+        let rows = stmt.query_map(params![limit as i64], entry_from_row)?;
+
+        rows.collect()
+    }
+
+    // Entries starting with `prefix`, newest first. Uses the command index.
+    pub fn search_prefix(&self, prefix: &str, limit: usize) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let upper = format!("{}\u{10ffff}", prefix);
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM history WHERE command >= ?1 AND command < ?2 ORDER BY id DESC LIMIT ?3", COLUMNS))?;
+        let rows = stmt.query_map(params![prefix, upper, limit as i64], entry_from_row)?;
+
+        rows.collect()
+    }
+
+    // Entries containing `pattern` anywhere, newest first
+    pub fn search(&self, pattern: &str, limit: usize) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let escaped = pattern.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM history WHERE command LIKE ?1 ESCAPE '\\' ORDER BY id DESC LIMIT ?2", COLUMNS))?;
+        let rows = stmt.query_map(params![format!("%{}%", escaped), limit as i64], entry_from_row)?;
+
+        rows.collect()
+    }
+
+    // Up to PAGE_SIZE entries with an id below `before`, newest first
+    fn page_before(&self, before: i64) -> rusqlite::Result<Vec<(i64, HistoryEntry)>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {}, id FROM history WHERE id < ?1 ORDER BY id DESC LIMIT ?2", COLUMNS))?;
+        let rows = stmt.query_map(params![before, PAGE_SIZE as i64], |row| Ok((row.get(6)?, entry_from_row(row)?)))?;
+
+        rows.collect()
+    }
+
+    // Delete every entry of `command`, returns how many were deleted
+    pub fn remove_command(&self, command: &str) -> rusqlite::Result<usize> {
+        self.conn.execute("DELETE FROM history WHERE command = ?1", params![command])
+    }
+
+    // Delete the rows stored for `entry`, matched like the in-memory history does
+    pub fn remove_entry(&self, entry: &HistoryEntry) -> rusqlite::Result<usize> {
+        self.conn.execute(
+            "DELETE FROM history WHERE command = ?1 AND timestamp = ?2 AND (?3 = '' OR session_id = '' OR session_id = ?3)",
+            params![entry.command, entry.unix_timestamp() as i64, entry.session_id],
+        )
+    }
+
+    pub fn clear(&self) -> rusqlite::Result<()> {
+        self.conn.execute("DELETE FROM history", [])?;
+        Ok(())
+    }
+}
+
+impl HistoryStore for SqliteHistory {
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()> {
+        SqliteHistory::append(self, entry).map_err(io::Error::other)
+    }
+
+    fn get(&self, index: usize) -> io::Result<Option<HistoryEntry>> {
+        SqliteHistory::get(self, index).map_err(io::Error::other)
+    }
+
+    fn len(&self) -> io::Result<usize> {
+        SqliteHistory::len(self).map_err(io::Error::other)
+    }
+
+    fn search(&self, pattern: &str) -> io::Result<Vec<HistoryEntry>> {
+        SqliteHistory::search(self, pattern, i64::MAX as usize).map_err(io::Error::other)
+    }
+
+    fn iter_newest(&self) -> io::Result<Box<dyn Iterator<Item = HistoryEntry> + '_>> {
+        let page = self.page_before(i64::MAX).map_err(io::Error::other)?;
+
+        Ok(Box::new(NewestPages {
+            history: self,
+            done: page.len() < PAGE_SIZE,
+            page: page.into_iter(),
+            before: i64::MAX,
+        }))
+    }
+
+    fn recent(&self, limit: usize) -> io::Result<Vec<HistoryEntry>> {
+        SqliteHistory::recent(self, limit).map_err(io::Error::other)
+    }
+
+    fn remove_command(&mut self, command: &str) -> io::Result<usize> {
+        SqliteHistory::remove_command(self, command).map_err(io::Error::other)
+    }
+
+    fn remove_entry(&mut self, entry: &HistoryEntry) -> io::Result<usize> {
+        SqliteHistory::remove_entry(self, entry).map_err(io::Error::other)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        SqliteHistory::clear(self).map_err(io::Error::other)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        // Every statement is committed right away
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqlite_history() {
+        let store = SqliteHistory::open_in_memory().unwrap();
+
+        for command in ["ls -la", "cargo build", "cargo test", "ls 100%"] {
+            let mut entry = HistoryEntry::new(command, "session");
+            entry.exit_status = Some(0);
+            store.append(&entry).unwrap();
+        }
+
+        assert_eq!(store.len().unwrap(), 4);
+        assert_eq!(store.get(1).unwrap().unwrap().command, "cargo build");
+        assert_eq!(store.get(4).unwrap(), None);
+
+        let recent: Vec<String> = store.recent(2).unwrap().into_iter().map(|e| e.command).collect();
+        assert_eq!(recent, vec!["cargo test", "ls 100%"]);
+
+        let found: Vec<String> = store.search_prefix("cargo", 10).unwrap().into_iter().map(|e| e.command).collect();
+        assert_eq!(found, vec!["cargo test", "cargo build"]);
+
+        assert_eq!(store.search("0%", 10).unwrap().len(), 1);
+        assert_eq!(store.search("_", 10).unwrap().len(), 0);
+
+        let removed = store.get(0).unwrap().unwrap();
+        assert_eq!(store.remove_entry(&HistoryEntry { session_id: "other".to_string(), ..removed.clone() }).unwrap(), 0);
+        assert_eq!(store.remove_entry(&removed).unwrap(), 1);
+        assert_eq!(store.len().unwrap(), 3);
+
+        store.clear().unwrap();
+        assert!(store.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_iter_newest_pages() {
+        let store = SqliteHistory::open_in_memory().unwrap();
+
+        for i in 0..PAGE_SIZE * 2 + 3 {
+            store.append(&HistoryEntry::new(&format!("echo {}", i), "session")).unwrap();
+        }
+
+        let newest: Vec<String> = HistoryStore::iter_newest(&store).unwrap().map(|e| e.command).collect();
+        assert_eq!(newest.len(), PAGE_SIZE * 2 + 3);
+        assert_eq!(newest[0], format!("echo {}", PAGE_SIZE * 2 + 2));
+        assert_eq!(newest[PAGE_SIZE], format!("echo {}", PAGE_SIZE + 2));
+        assert_eq!(newest.last().unwrap(), "echo 0");
+
+        let recent: Vec<String> = HistoryStore::recent(&store, 2).unwrap().into_iter().map(|e| e.command).collect();
+        assert_eq!(recent, vec![format!("echo {}", PAGE_SIZE * 2 + 1), format!("echo {}", PAGE_SIZE * 2 + 2)]);
+    }
+}
@@ -22,17 +22,37 @@
 * SOFTWARE.
 */
 
-// Managing the input data
-pub(crate) struct InputData {
-    pub(crate) data: String,
-    pub(crate) len: usize
+// Exit status a command callback reports back to the navigator
+pub trait CommandStatus {
+    fn status(&self) -> Option<i32>;
 }
 
-impl InputData {
-    pub(crate) fn new(data: String, len: usize) -> Self {
-        InputData { 
-            data, 
-            len 
-        }
+impl CommandStatus for () {
+    fn status(&self) -> Option<i32> {
+        None
     }
-}
\ No newline at end of file
+}
+
+impl CommandStatus for i32 {
+    fn status(&self) -> Option<i32> {
+        Some(*self)
+    }
+}
+
+impl CommandStatus for Option<i32> {
+    fn status(&self) -> Option<i32> {
+        *self
+    }
+}
+
+impl CommandStatus for bool {
+    fn status(&self) -> Option<i32> {
+        Some(if *self { 0 } else { 1 })
+    }
+}
+
+impl<T, E> CommandStatus for Result<T, E> {
+    fn status(&self) -> Option<i32> {
+        Some(if self.is_ok() { 0 } else { 1 })
+    }
+}
@@ -0,0 +1,304 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::io;
+
+use super::format::LogFormat;
+use super::history::{self, HistoryEntry};
+use super::log::LogStream;
+
+// Storage the history is persisted in. Indexes count from the oldest entry.
+// Implement it to keep the history somewhere else, e.g. on a server or encrypted.
+pub trait HistoryStore {
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()>;
+
+    fn get(&self, index: usize) -> io::Result<Option<HistoryEntry>>;
+
+    fn len(&self) -> io::Result<usize>;
+
+    fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    // Entries containing `pattern`, newest first
+    fn search(&self, pattern: &str) -> io::Result<Vec<HistoryEntry>>;
+
+    fn iter_newest(&self) -> io::Result<Box<dyn Iterator<Item = HistoryEntry> + '_>>;
+
+    // The newest `limit` entries, oldest first
+    fn recent(&self, limit: usize) -> io::Result<Vec<HistoryEntry>> {
+        let mut entries: Vec<HistoryEntry> = self.iter_newest()?.take(limit).collect();
+        entries.reverse();
+        Ok(entries)
+    }
+
+    // Remove every entry of `command`, returns how many were removed
+    fn remove_command(&mut self, command: &str) -> io::Result<usize>;
+
+    // Remove the entries stored for `entry`, returns how many were removed.
+    // The default rewrites the whole store, implementations can do better.
+    fn remove_entry(&mut self, entry: &HistoryEntry) -> io::Result<usize> {
+        let mut entries: Vec<HistoryEntry> = self.iter_newest()?.collect();
+        let len = entries.len();
+        entries.retain(|known| !history::same_entry(known, entry));
+
+        if entries.len() < len {
+            self.clear()?;
+            for known in entries.iter().rev() {
+                self.append(known)?;
+            }
+        }
+
+        Ok(len - entries.len())
+    }
+
+    fn clear(&mut self) -> io::Result<()>;
+
+    // Write out pending changes and pick up changes made by others
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+// History which lives as long as the process does
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    entries: Vec<HistoryEntry>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore { 
+            entries: Vec::new() 
+        }
+    }
+}
+
+fn search_entries(entries: &[HistoryEntry], pattern: &str) -> Vec<HistoryEntry> {
+    entries.iter()
+        .rev()
+        .filter(|entry| entry.command.contains(pattern))
+        .cloned()
+        .collect()
+}
+
+impl HistoryStore for MemoryStore {
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()> {
+        self.entries.push(entry.clone());
+        Ok(())
+    }
+
+    fn get(&self, index: usize) -> io::Result<Option<HistoryEntry>> {
+        Ok(self.entries.get(index).cloned())
+    }
+
+    fn len(&self) -> io::Result<usize> {
+        Ok(self.entries.len())
+    }
+
+    fn search(&self, pattern: &str) -> io::Result<Vec<HistoryEntry>> {
+        Ok(search_entries(&self.entries, pattern))
+    }
+
+    fn iter_newest(&self) -> io::Result<Box<dyn Iterator<Item = HistoryEntry> + '_>> {
+        Ok(Box::new(self.entries.iter().rev().cloned()))
+    }
+
+    fn remove_command(&mut self, command: &str) -> io::Result<usize> {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.command != command);
+        Ok(len - self.entries.len())
+    }
+
+    fn remove_entry(&mut self, entry: &HistoryEntry) -> io::Result<usize> {
+        let len = self.entries.len();
+        self.entries.retain(|known| !history::same_entry(known, entry));
+        Ok(len - self.entries.len())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// History file in one of the LogFormat layouts. The entries are read once
+// when opening and kept in memory, new entries are appended to the file.
+pub struct FileStore {
+    stream: LogStream,
+    entries: Vec<HistoryEntry>,
+    // How far the file has been read into `entries`
+    offset: u64,
+}
+
+impl FileStore {
+    // One command per line without metadata
+    pub fn plain(path: &str) -> io::Result<Self> {
+        FileStore::structured(path, LogFormat::Plain)
+    }
+
+    // Commands together with their metadata as far as the format supports it
+    pub fn structured(path: &str, format: LogFormat) -> io::Result<Self> {
+        let mut stream = LogStream::new(path.to_string(), format);
+        stream.create_log_file()?;
+
+        let (entries, offset) = stream.read_log_file(0)?;
+        Ok(FileStore { 
+            stream, 
+            entries,
+            offset,
+        })
+    }
+}
+
+impl HistoryStore for FileStore {
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()> {
+        match self.stream.append_log_file(entry)? {
+            // Nobody wrote to the file since it was last read
+            Some((start, end)) if start == self.offset => {
+                self.entries.push(entry.clone());
+                self.offset = end;
+                Ok(())
+            }
+            _ => self.sync()
+        }
+    }
+
+    fn get(&self, index: usize) -> io::Result<Option<HistoryEntry>> {
+        Ok(self.entries.get(index).cloned())
+    }
+
+    fn len(&self) -> io::Result<usize> {
+        Ok(self.entries.len())
+    }
+
+    fn search(&self, pattern: &str) -> io::Result<Vec<HistoryEntry>> {
+        Ok(search_entries(&self.entries, pattern))
+    }
+
+    fn iter_newest(&self) -> io::Result<Box<dyn Iterator<Item = HistoryEntry> + '_>> {
+        Ok(Box::new(self.entries.iter().rev().cloned()))
+    }
+
+    fn remove_command(&mut self, command: &str) -> io::Result<usize> {
+        let len = self.entries.len();
+        (self.entries, self.offset) = self.stream.retain_log_file(|entry| entry.command != command)?;
+        Ok(len.saturating_sub(self.entries.len()))
+    }
+
+    fn remove_entry(&mut self, entry: &HistoryEntry) -> io::Result<usize> {
+        let len = self.entries.len();
+        (self.entries, self.offset) = self.stream.retain_log_file(|known| !history::same_entry(known, entry))?;
+        Ok(len.saturating_sub(self.entries.len()))
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.stream.truncate_log_file()?;
+        self.entries.clear();
+        self.offset = 0;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        // Only read what other sessions appended since the last read
+        let (entries, end) = self.stream.read_log_file(self.offset)?;
+
+        if end < self.offset {
+            // Truncated by someone else, start over
+            (self.entries, self.offset) = self.stream.read_log_file(0)?;
+        } else {
+            self.entries.extend(entries);
+            self.offset = end;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_file_store() {
+        let path = std::env::temp_dir().join(format!("clihistory_store_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let mut store = FileStore::structured(path, LogFormat::JsonLines).unwrap();
+        for command in ["ls", "cargo build", "cargo test"] {
+            store.append(&HistoryEntry::new(command, "session")).unwrap();
+        }
+
+        let reopened = FileStore::structured(path, LogFormat::JsonLines).unwrap();
+        assert_eq!(reopened.len().unwrap(), 3);
+        assert_eq!(reopened.get(0).unwrap().unwrap().command, "ls");
+
+        let newest: Vec<String> = reopened.iter_newest().unwrap().map(|entry| entry.command).collect();
+        assert_eq!(newest, vec!["cargo test", "cargo build", "ls"]);
+        assert_eq!(reopened.search("cargo").unwrap().len(), 2);
+
+        store.append(&HistoryEntry::new("ls", "session")).unwrap();
+        assert_eq!(store.remove_command("ls").unwrap(), 2);
+        let reopened = FileStore::structured(path, LogFormat::JsonLines).unwrap();
+        assert_eq!(reopened.len().unwrap(), 2);
+
+        store.clear().unwrap();
+        store.sync().unwrap();
+        assert!(store.is_empty().unwrap());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_file_store_sync() {
+        let path = std::env::temp_dir().join(format!("clihistory_sync_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let mut first = FileStore::structured(path, LogFormat::JsonLines).unwrap();
+        let mut second = FileStore::structured(path, LogFormat::JsonLines).unwrap();
+
+        first.append(&HistoryEntry::new("ls", "a")).unwrap();
+        second.append(&HistoryEntry::new("pwd", "b")).unwrap();
+        assert_eq!(second.len().unwrap(), 2);
+
+        first.append(&HistoryEntry::new("make", "a")).unwrap();
+        first.sync().unwrap();
+        let commands: Vec<String> = first.recent(3).unwrap().into_iter().map(|entry| entry.command).collect();
+        assert_eq!(commands, vec!["ls", "pwd", "make"]);
+
+        // Truncated by the other session
+        second.clear().unwrap();
+        second.append(&HistoryEntry::new("cd", "b")).unwrap();
+        first.sync().unwrap();
+        assert_eq!(first.get(0).unwrap().unwrap().command, "cd");
+        assert_eq!(first.len().unwrap(), 1);
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+/* 
+* MIT License
+* 
+* Copyright (c) 2025 f42h
+* 
+* Permission is hereby granted, free of charge, to any person obtaining a copy
+* of this software and associated documentation files (the "Software"), to deal
+* in the Software without restriction, including without limitation the rights
+* to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+* copies of the Software, and to permit persons to whom the Software is
+* furnished to do so, subject to the following conditions:
+* 
+* The above copyright notice and this permission notice shall be included in all
+* copies or substantial portions of the Software.
+* 
+* THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+* IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+* FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+* AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+* LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+* OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+* SOFTWARE.
+*/
+
+use std::error::Error;
+use std::fmt;
+
+// Why a line couldn't be split into arguments
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenizeError {
+    // A quote opened at char `position` is never closed
+    UnterminatedQuote { quote: char, position: usize },
+    // The line ends with a backslash
+    DanglingEscape,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::UnterminatedQuote { quote, position } => {
+                write!(f, "unterminated {} quote opened at position {}", if *quote == '\'' { "single" } else { "double" }, position)
+            }
+            TokenizeError::DanglingEscape => write!(f, "the line ends with an escaping backslash")
+        }
+    }
+}
+
+impl Error for TokenizeError {}
+
+// Split `line` into arguments like a POSIX shell: whitespace separates arguments,
+// single quotes keep everything literally, double quotes allow \" \\ \$ and \` escapes
+// and a backslash outside of quotes escapes any char. `a"b c"'d'` is one argument.
+pub fn tokenize(line: &str) -> Result<Vec<String>, TokenizeError> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false; // Quotes make an argument even if it stays empty
+    let mut chars = line.chars().enumerate();
+
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => current.push(c),
+                        None => return Err(TokenizeError::UnterminatedQuote { quote: '\'', position })
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c @ ('"' | '\\' | '$' | '`'))) => current.push(c),
+                            Some((_, c)) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err(TokenizeError::UnterminatedQuote { quote: '"', position })
+                        },
+                        Some((_, c)) => current.push(c),
+                        None => return Err(TokenizeError::UnterminatedQuote { quote: '"', position })
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                match chars.next() {
+                    Some((_, c)) => current.push(c),
+                    None => return Err(TokenizeError::DanglingEscape)
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                in_arg = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(current);
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("  ls   -la  src ").unwrap(), vec!["ls", "-la", "src"]);
+        assert_eq!(tokenize(r#"echo 'a  b' "c \"d\" \n" e\ f"#).unwrap(), vec!["echo", "a  b", r#"c "d" \n"#, "e f"]);
+        assert_eq!(tokenize(r#"x"y z"'w' "" ''"#).unwrap(), vec!["xy zw", "", ""]);
+        assert!(tokenize("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        assert_eq!(tokenize("echo 'abc"), Err(TokenizeError::UnterminatedQuote { quote: '\'', position: 5 }));
+        assert_eq!(tokenize(r#"say "hi"#), Err(TokenizeError::UnterminatedQuote { quote: '"', position: 4 }));
+        assert_eq!(tokenize("trailing \\"), Err(TokenizeError::DanglingEscape));
+    }
+}